@@ -0,0 +1,488 @@
+//! User configuration
+//!
+//! Zellij doesn't pass any configuration to plugins, so we read it from
+//! `$XDG_CONFIG_HOME/zellij-cpulamp/config` instead. The file consists of
+//! `key = value` lines. Empty lines and lines starting with `#` are ignored.
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    pattern::NameFilter,
+    sysinfo::{Backend, CpuTime, CpuTimeSet},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// Where to get the CPU usage from
+    pub backend: Backend,
+    /// How to arrange the CPU lamps
+    pub layout: Layout,
+    /// How to show the CPUs outside Zellij's CPU affinity
+    pub cpuset: CpusetMode,
+    /// How to distinguish performance and efficiency cores
+    pub core_types: CoreTypes,
+    /// Show a lamp for each NUMA node that blinks with the mean usage of its
+    /// CPUs beside the lamps
+    pub node_lamps: bool,
+    /// Color the lamps of CPUs isolated by the `isolcpus` or `nohz_full` boot
+    /// parameters
+    pub isolated_tint: bool,
+    /// How to alert when an isolated CPU's usage (per [`Self::lamp_times`])
+    /// exceeds [`Self::isolated_threshold`]
+    pub isolated_alert: Alert,
+    /// Measured in percent
+    pub isolated_threshold: f64,
+    /// What makes the CPU lamps blink
+    pub lamp_metric: LampMetric,
+    /// The CPU time categories that make the lamps blink
+    pub lamp_times: CpuTimeSet,
+    /// The CPU time categories that make the lamps blink in an accent color
+    pub accent_times: CpuTimeSet,
+    /// Dim the lamps of CPUs running at low clock frequencies and brighten
+    /// those running at high ones
+    pub freq_intensity: bool,
+    /// Color the lamps of CPUs whose cores are hotter than
+    /// [`Self::temp_warn`] or [`Self::temp_critical`]
+    pub temp_tint: bool,
+    /// Measured in degrees Celsius
+    pub temp_warn: f64,
+    /// Measured in degrees Celsius
+    pub temp_critical: f64,
+    /// Show a memory usage gauge beside the lamps
+    pub memory_gauge: bool,
+    /// Show a lamp that blinks with swap activity beside the lamps
+    pub swap_lamp: bool,
+    /// How to show the 1-minute load average beside the lamps
+    pub load_segment: LoadSegment,
+    /// How to show the kernel activity rates beside the lamps
+    pub activity_segment: ActivitySegment,
+    /// Show the name and CPU usage of the process using the most CPU time
+    /// beside the lamps
+    pub process_segment: bool,
+    /// Show the lit CPU lamps in a different color in proportion to the
+    /// Zellij session's share of the CPU usage
+    pub session_overlay: bool,
+    /// How to alert when the resource pressure exceeds
+    /// [`Self::pressure_threshold`]
+    pub pressure_alert: Alert,
+    /// The threshold of the `some avg10` value of any resource's pressure,
+    /// measured in percent
+    pub pressure_threshold: f64,
+    /// How to show the power draw beside the lamps
+    pub power_segment: PowerSegment,
+    /// Show the battery charge level and a lamp that blinks faster as the
+    /// battery nears empty beside the lamps
+    pub battery_segment: bool,
+    /// How to show the network throughput beside the lamps
+    pub net_segment: NetSegment,
+    /// The network interfaces to include in [`Self::net_segment`]
+    pub net_filter: NameFilter,
+    /// Show a lamp for each block device that blinks with its utilization
+    pub disk_lamps: bool,
+    /// The block devices to show lamps for
+    pub disk_filter: NameFilter,
+    /// Show lamps for partitions as well as whole disks
+    pub disk_partitions: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// Place the CPUs in the order of CPU IDs, packing several CPUs into a
+    /// Braille cell if they don't fit.
+    Sequential,
+    /// Place the SMT siblings of each core in a Braille cell, with one column
+    /// per core and one block per package. Falls back to [`Self::Sequential`]
+    /// if the blocks don't fit.
+    Topology,
+    /// Place the CPUs of each NUMA node in a band of rows, or in a block of
+    /// columns if there are more nodes than rows.
+    Numa,
+    /// Show a lamp per Zellij pane instead of per CPU, blinking with the CPU
+    /// usage of the processes running in the pane.
+    Panes,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpusetMode {
+    /// Show them like the other CPUs.
+    Show,
+    /// Show them in a dim color.
+    Dim,
+    /// Don't show them.
+    Hide,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoreTypes {
+    /// Show them alike.
+    Same,
+    /// Show efficiency cores with a hollow glyph.
+    Glyph,
+    /// Show efficiency cores in a different color.
+    Color,
+    /// Show performance and efficiency cores in separate rows (or blocks with
+    /// [`Layout::Topology`]).
+    Rows,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LampMetric {
+    /// The CPU usage counting [`Config::lamp_times`]
+    Usage,
+    /// The rate of hardware interrupts handled by the CPU
+    Irq,
+    /// The rate of softirqs handled by the CPU
+    Softirq,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadSegment {
+    Hidden,
+    /// Show the load average as a number.
+    Number,
+    /// Show the load average divided by the number of online CPUs as a gauge.
+    Gauge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActivitySegment {
+    Hidden,
+    /// Show the context switch, interrupt, softirq, and fork rates as numbers.
+    Number,
+    /// Show a lamp that blinks with the fork rate.
+    Lamp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerSegment {
+    Hidden,
+    /// Show the package power.
+    Package,
+    /// Show the package, core, and DRAM power.
+    All,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetSegment {
+    Hidden,
+    /// Show the total throughput of all selected interfaces.
+    Total,
+    /// Show the throughput of each selected interface.
+    Interfaces,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alert {
+    Off,
+    /// Tint the lamp area.
+    Tint,
+    /// Blink the lamp area.
+    Blink,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            backend: Backend::Host,
+            layout: Layout::Sequential,
+            cpuset: CpusetMode::Show,
+            core_types: CoreTypes::Same,
+            node_lamps: false,
+            isolated_tint: false,
+            isolated_alert: Alert::Off,
+            isolated_threshold: 10.0,
+            lamp_metric: LampMetric::Usage,
+            lamp_times: [
+                CpuTime::User,
+                CpuTime::Nice,
+                CpuTime::System,
+                CpuTime::Irq,
+                CpuTime::Softirq,
+                CpuTime::Steal,
+            ]
+            .into_iter()
+            .collect(),
+            accent_times: CpuTimeSet::EMPTY,
+            freq_intensity: false,
+            temp_tint: false,
+            temp_warn: 80.0,
+            temp_critical: 95.0,
+            memory_gauge: false,
+            swap_lamp: false,
+            load_segment: LoadSegment::Hidden,
+            activity_segment: ActivitySegment::Hidden,
+            process_segment: false,
+            session_overlay: false,
+            pressure_alert: Alert::Off,
+            pressure_threshold: 10.0,
+            power_segment: PowerSegment::Hidden,
+            battery_segment: false,
+            net_segment: NetSegment::Hidden,
+            net_filter: NameFilter::new(&["*"], &["lo"]),
+            disk_lamps: false,
+            disk_filter: NameFilter::new(&["*"], &["loop*", "ram*"]),
+            disk_partitions: false,
+        }
+    }
+}
+
+impl Config {
+    /// Load the configuration file. Falls back to the default configuration
+    /// if the file is missing or invalid.
+    pub fn load() -> Self {
+        let text = crate::process::spawn_and_get_output(
+            b"cat \"${XDG_CONFIG_HOME:-${HOME:-}/.config}/zellij-cpulamp/config\" \
+            2> /dev/null || true",
+        );
+        std::str::from_utf8(&text)
+            .map_err(anyhow::Error::from)
+            .and_then(Self::parse)
+            .unwrap_or_else(|e| {
+                eprintln!("Failed to load the configuration: {e:?}");
+                Self::default()
+            })
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut this = Self::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            this.parse_line(line)
+                .with_context(|| format!("line {}: '{line}'", i + 1))?;
+        }
+        Ok(this)
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<()> {
+        let (key, value) = line.split_once('=').context("'=' is absent")?;
+        let value = value.trim();
+        match key.trim() {
+            "backend" => {
+                self.backend = match value {
+                    "host" => Backend::Host,
+                    "cgroup" => Backend::Cgroup,
+                    _ => bail!("expected 'host' or 'cgroup', found '{value}'"),
+                }
+            }
+            "layout" => {
+                self.layout = match value {
+                    "sequential" => Layout::Sequential,
+                    "topology" => Layout::Topology,
+                    "numa" => Layout::Numa,
+                    "panes" => Layout::Panes,
+                    _ => bail!(
+                        "expected 'sequential', 'topology', 'numa', or 'panes', found '{value}'"
+                    ),
+                }
+            }
+            "cpuset" => {
+                self.cpuset = match value {
+                    "show" => CpusetMode::Show,
+                    "dim" => CpusetMode::Dim,
+                    "hide" => CpusetMode::Hide,
+                    _ => bail!("expected 'show', 'dim', or 'hide', found '{value}'"),
+                }
+            }
+            "core_types" => {
+                self.core_types = match value {
+                    "same" => CoreTypes::Same,
+                    "glyph" => CoreTypes::Glyph,
+                    "color" => CoreTypes::Color,
+                    "rows" => CoreTypes::Rows,
+                    _ => bail!("expected 'same', 'glyph', 'color', or 'rows', found '{value}'"),
+                }
+            }
+            "node_lamps" => self.node_lamps = parse_bool(value)?,
+            "isolated_tint" => self.isolated_tint = parse_bool(value)?,
+            "isolated_alert" => self.isolated_alert = parse_alert(value)?,
+            "isolated_threshold" => self.isolated_threshold = parse_number(value)?,
+            "lamp_metric" => {
+                self.lamp_metric = match value {
+                    "usage" => LampMetric::Usage,
+                    "irq" => LampMetric::Irq,
+                    "softirq" => LampMetric::Softirq,
+                    _ => bail!("expected 'usage', 'irq', or 'softirq', found '{value}'"),
+                }
+            }
+            "lamp_times" => self.lamp_times = parse_cpu_time_set(value)?,
+            "accent_times" => self.accent_times = parse_cpu_time_set(value)?,
+            "freq_intensity" => self.freq_intensity = parse_bool(value)?,
+            "temp_tint" => self.temp_tint = parse_bool(value)?,
+            "temp_warn" => self.temp_warn = parse_number(value)?,
+            "temp_critical" => self.temp_critical = parse_number(value)?,
+            "memory_gauge" => self.memory_gauge = parse_bool(value)?,
+            "swap_lamp" => self.swap_lamp = parse_bool(value)?,
+            "load_segment" => {
+                self.load_segment = match value {
+                    "hidden" => LoadSegment::Hidden,
+                    "number" => LoadSegment::Number,
+                    "gauge" => LoadSegment::Gauge,
+                    _ => bail!("expected 'hidden', 'number', or 'gauge', found '{value}'"),
+                }
+            }
+            "activity_segment" => {
+                self.activity_segment = match value {
+                    "hidden" => ActivitySegment::Hidden,
+                    "number" => ActivitySegment::Number,
+                    "lamp" => ActivitySegment::Lamp,
+                    _ => bail!("expected 'hidden', 'number', or 'lamp', found '{value}'"),
+                }
+            }
+            "process_segment" => self.process_segment = parse_bool(value)?,
+            "session_overlay" => self.session_overlay = parse_bool(value)?,
+            "pressure_alert" => self.pressure_alert = parse_alert(value)?,
+            "pressure_threshold" => self.pressure_threshold = parse_number(value)?,
+            "power_segment" => {
+                self.power_segment = match value {
+                    "hidden" => PowerSegment::Hidden,
+                    "package" => PowerSegment::Package,
+                    "all" => PowerSegment::All,
+                    _ => bail!("expected 'hidden', 'package', or 'all', found '{value}'"),
+                }
+            }
+            "battery_segment" => self.battery_segment = parse_bool(value)?,
+            "net_segment" => {
+                self.net_segment = match value {
+                    "hidden" => NetSegment::Hidden,
+                    "total" => NetSegment::Total,
+                    "interfaces" => NetSegment::Interfaces,
+                    _ => bail!("expected 'hidden', 'total', or 'interfaces', found '{value}'"),
+                }
+            }
+            "net_include" => self.net_filter.include = parse_list(value),
+            "net_exclude" => self.net_filter.exclude = parse_list(value),
+            "disk_lamps" => self.disk_lamps = parse_bool(value)?,
+            "disk_include" => self.disk_filter.include = parse_list(value),
+            "disk_exclude" => self.disk_filter.exclude = parse_list(value),
+            "disk_partitions" => self.disk_partitions = parse_bool(value)?,
+            key => bail!("unknown key '{key}'"),
+        }
+        Ok(())
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => bail!("expected 'true' or 'false', found '{value}'"),
+    }
+}
+
+fn parse_alert(value: &str) -> Result<Alert> {
+    match value {
+        "off" => Ok(Alert::Off),
+        "tint" => Ok(Alert::Tint),
+        "blink" => Ok(Alert::Blink),
+        _ => bail!("expected 'off', 'tint', or 'blink', found '{value}'"),
+    }
+}
+
+fn parse_number(value: &str) -> Result<f64> {
+    value
+        .parse()
+        .with_context(|| format!("expected a number, found '{value}'"))
+}
+
+/// Parse a comma-separated list.
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Parse a comma-separated list of [`CpuTime`] names.
+fn parse_cpu_time_set(value: &str) -> Result<CpuTimeSet> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| CpuTime::from_name(name).with_context(|| format!("unknown CPU time '{name}'")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+
+        let config = Config::parse(
+            "# comment\n\
+            \n\
+            backend = cgroup\n\
+            layout = numa\n\
+            cpuset = hide\n\
+            core_types = rows\n\
+            node_lamps = true\n\
+            isolated_tint = true\n\
+            isolated_alert = tint\n\
+            isolated_threshold = 1\n\
+            lamp_metric = softirq\n\
+            lamp_times = user, system\n\
+            accent_times=steal\n\
+            freq_intensity = true\n\
+            temp_tint = true\n\
+            temp_critical = 90\n\
+            memory_gauge = true\n\
+            load_segment = gauge\n\
+            activity_segment = lamp\n\
+            process_segment = true\n\
+            session_overlay = true\n\
+            pressure_alert = blink\n\
+            pressure_threshold = 2.5\n\
+            power_segment = all\n\
+            battery_segment = true\n\
+            net_exclude = lo, veth*\n\
+            disk_lamps = true\n\
+            disk_include = sd*, nvme*\n",
+        )
+        .unwrap();
+        assert_eq!(config.backend, Backend::Cgroup);
+        assert_eq!(config.layout, Layout::Numa);
+        assert_eq!(config.cpuset, CpusetMode::Hide);
+        assert_eq!(config.core_types, CoreTypes::Rows);
+        assert!(config.node_lamps);
+        assert!(config.isolated_tint);
+        assert_eq!(config.isolated_alert, Alert::Tint);
+        assert_eq!(config.isolated_threshold, 1.0);
+        assert_eq!(config.lamp_metric, LampMetric::Softirq);
+        assert_eq!(
+            config.lamp_times,
+            [CpuTime::User, CpuTime::System].into_iter().collect()
+        );
+        assert_eq!(config.accent_times, [CpuTime::Steal].into_iter().collect());
+        assert!(config.freq_intensity);
+        assert!(config.temp_tint);
+        assert_eq!(config.temp_warn, 80.0);
+        assert_eq!(config.temp_critical, 90.0);
+        assert!(config.memory_gauge);
+        assert_eq!(config.load_segment, LoadSegment::Gauge);
+        assert_eq!(config.activity_segment, ActivitySegment::Lamp);
+        assert!(config.process_segment);
+        assert!(config.session_overlay);
+        assert_eq!(config.pressure_alert, Alert::Blink);
+        assert_eq!(config.pressure_threshold, 2.5);
+        assert_eq!(config.power_segment, PowerSegment::All);
+        assert!(config.battery_segment);
+        assert_eq!(config.net_filter, NameFilter::new(&["*"], &["lo", "veth*"]));
+        assert!(config.disk_lamps);
+        assert_eq!(
+            config.disk_filter,
+            NameFilter::new(&["sd*", "nvme*"], &["loop*", "ram*"])
+        );
+
+        assert!(Config::parse("lamp_times = user, bogus").is_err());
+        assert!(Config::parse("bogus = 1").is_err());
+        assert!(Config::parse("lamp_times").is_err());
+        assert!(Config::parse("memory_gauge = 1").is_err());
+        assert!(Config::parse("pressure_threshold = high").is_err());
+    }
+}
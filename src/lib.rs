@@ -1,5 +1,7 @@
 //! Utilities for the plugin
+pub mod config;
 pub mod iter;
+pub mod pattern;
 mod process;
 pub mod slist;
 pub mod sysinfo;
@@ -1,29 +1,140 @@
 //! Plugin entry point
 use num_integer::div_ceil;
-use std::time::Instant;
+use std::{fmt::Write as _, time::Instant};
 use zellij_tile::prelude::*;
 use zellij_tile_utils::style;
 
-use zellij_cpulamp::{slist, sysinfo};
+use zellij_cpulamp::{
+    config::{
+        ActivitySegment, Alert, Config, CoreTypes, CpusetMode, LampMetric, Layout, LoadSegment,
+        NetSegment, PowerSegment,
+    },
+    slist,
+    sysinfo::{self, BatteryStatus},
+};
 
 struct State {
     mode_info: ModeInfo,
+    config: Config,
     sysinfo: Box<dyn sysinfo::System>,
     elapsed_since_last_frame_us: u32,
     elapsed_since_last_measure_f: u32,
     last_timeout: Instant,
     cpus: slist::Link<CpuState>,
+    /// Indicates whether a CPU went online or offline since the last
+    /// successful [`sysinfo::System::refresh_topology`] call.
+    topology_stale: bool,
+    /// Blinks with swap activity.
+    swap_lamp: Lamp,
+    /// Blinks with the fork rate.
+    activity_lamp: Lamp,
+    /// Blinks faster as the battery nears empty while discharging.
+    battery_lamp: Lamp,
+    /// Blink with the utilization of the block devices selected by
+    /// [`Config::disk_filter`].
+    disk_lamps: slist::Link<Lamp>,
+    /// Blink with the mean CPU usage of each NUMA node, in the order of node
+    /// IDs.
+    node_lamps: slist::Link<Lamp>,
+    /// The PID of the Zellij server, whose children are the panes' shells
+    zellij_pid: u32,
+    /// Blink with the CPU usage of each pane's processes if [`Config::layout`]
+    /// is [`Layout::Panes`].
+    pane_lamps: slist::Link<Lamp>,
+    /// Indicates whether the resource pressure exceeds
+    /// [`Config::pressure_threshold`].
+    pressure_high: bool,
+    /// Indicates whether an isolated CPU's usage exceeds
+    /// [`Config::isolated_threshold`].
+    isolated_busy: bool,
+    /// Blinks while any alert configured as [`Alert::Blink`] is active.
+    alert_lamp: Lamp,
     output_buffer: String,
 }
 
+#[derive(Default)]
 struct CpuState {
+    /// Blinks according to [`Config::lamp_times`].
+    lamp: Lamp,
+    /// Blinks according to [`Config::accent_times`].
+    accent: Lamp,
+    /// Indicates whether this CPU's latest usage is unknown.
+    unknown: bool,
+    online: bool,
+    /// Indicates whether Zellij's CPU affinity includes this CPU.
+    allowed: bool,
+    /// Indicates whether this CPU is isolated by `isolcpus` or `nohz_full`.
+    isolated: bool,
+    /// `None` if it's unknown or neither [`Config::layout`] nor
+    /// [`Config::core_types`] needs it.
+    topology: Option<sysinfo::CpuTopology>,
+    /// Where the clock frequency lies between its bounds (`0.0..=1.0`).
+    /// `None` if it's unknown or [`Config::freq_intensity`] is disabled.
+    freq: Option<f64>,
+    /// [`Tint::Warm`] or [`Tint::Hot`] if this CPU's core is hotter than the
+    /// thresholds and [`Config::temp_tint`] is enabled
+    heat: Option<Tint>,
+    /// Lights up on the fraction of `lamp`'s lit frames that represents the
+    /// Zellij session's share of the CPU usage
+    session: Lamp,
+}
+
+/// A blinking indicator. `rate` is added to `charge` on every frame, and the
+/// lamp lights up on the frames where `charge` overflows.
+#[derive(Default)]
+struct Lamp {
     charge: u32,
     rate: u32,
-    /// Indicates whether this CPU's usage indicator is active for the current
-    /// frame.
+    /// Indicates whether this lamp is active for the current frame.
     lit: bool,
 }
 
+/// The color of a lit lamp, in the ascending order of precedence, or a
+/// segment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Tint {
+    /// Used for offline CPUs
+    Offline,
+    /// Used for CPUs whose usage is unknown
+    Unknown,
+    /// Used for CPUs outside Zellij's CPU affinity if [`Config::cpuset`] is
+    /// [`CpusetMode::Dim`]
+    Excluded,
+    /// [`Self::Normal`] for CPUs running at low clock frequencies
+    Slow,
+    Normal,
+    /// [`Self::Normal`] for CPUs running at high clock frequencies
+    Fast,
+    /// Used for efficiency cores if [`Config::core_types`] is
+    /// [`CoreTypes::Color`]
+    Efficient,
+    /// Used for the Zellij session's share of the lit lamps if
+    /// [`Config::session_overlay`] is enabled
+    Session,
+    Accent,
+    /// Used for isolated CPUs if [`Config::isolated_tint`] is enabled
+    Isolated,
+    /// Used for CPUs hotter than [`Config::temp_warn`]
+    Warm,
+    /// Used for CPUs hotter than [`Config::temp_critical`]
+    Hot,
+    Gauge,
+    Swap,
+    Disk,
+}
+
+/// A widget displayed to the right of the lamps
+enum Segment {
+    /// A one-column vertical bar filled to the given fraction
+    Gauge(f64, Tint),
+    /// A lamp, which is displayed on the bottom row
+    Lamp(Option<Tint>),
+    /// Lamps arranged in rows from the top
+    Lamps(Vec<Option<Tint>>),
+    /// A text, which is displayed on the bottom row
+    Text(String, Tint),
+}
+
 register_plugin!(State);
 
 /// The unit of time used for various operations in this plugin.
@@ -33,23 +144,375 @@ const FRAME_INTERVAL_US: u32 = 200_000;
 /// (FRAME_INTERVAL_US).
 const MEASURE_INTERVAL_F: u32 = 5;
 
+/// The swap activity (swapped-in plus swapped-out pages per second) that makes
+/// the swap lamp stay lit
+const SWAP_LAMP_FULL_SCALE: f64 = 10_000.0;
+
+/// The interrupts per second that make a CPU lamp stay lit with
+/// [`LampMetric::Irq`] or [`LampMetric::Softirq`]
+const IRQ_LAMP_FULL_SCALE: f64 = 100_000.0;
+
+/// The forks per second that make the activity lamp stay lit
+const FORK_LAMP_FULL_SCALE: f64 = 1_000.0;
+
+/// Format a load average in about four characters.
+fn format_load(load: f64) -> String {
+    if load < 10.0 {
+        format!("{load:.2}")
+    } else if load < 100.0 {
+        format!("{load:.1}")
+    } else {
+        format!("{load:.0}")
+    }
+}
+
+/// Format a quantity with a binary prefix in at most four characters.
+fn format_binary(value: f64) -> String {
+    let mut value = value.max(0.0);
+    for prefix in ["", "K", "M", "G", "T"] {
+        if value < 10.0 && !prefix.is_empty() {
+            return format!("{value:.1}{prefix}");
+        } else if value < 1000.0 {
+            return format!("{value:.0}{prefix}");
+        }
+        value /= 1024.0;
+    }
+    format!("{value:.0}P")
+}
+
+/// Format a quantity with a decimal prefix in at most four characters.
+fn format_decimal(value: f64) -> String {
+    let mut value = value.max(0.0);
+    for prefix in ["", "k", "M", "G"] {
+        if value < 10.0 && !prefix.is_empty() {
+            return format!("{value:.1}{prefix}");
+        } else if value < 1000.0 {
+            return format!("{value:.0}{prefix}");
+        }
+        value /= 1000.0;
+    }
+    format!("{value:.0}T")
+}
+
+/// Format a power draw, e.g., `12.3W`.
+fn format_watts(watts: Option<f64>) -> String {
+    watts.map_or_else(
+        || "?W".to_owned(),
+        |watts| format!("{}W", format_load(watts)),
+    )
+}
+
+fn format_net_rates(rates: &sysinfo::NetRates) -> String {
+    format!(
+        "↓{}↑{}",
+        format_binary(rates.rx_bytes),
+        format_binary(rates.tx_bytes)
+    )
+}
+
+/// Get the mean charge level and the overall status of all batteries. A
+/// discharging battery takes precedence over a charging one, which takes
+/// precedence over the rest.
+fn battery_summary(system: &dyn sysinfo::System) -> Option<(f64, BatteryStatus)> {
+    let rank = |status| match status {
+        BatteryStatus::Discharging => 2,
+        BatteryStatus::Charging => 1,
+        _ => 0,
+    };
+    let (mut sum, mut count, mut status) = (0.0, 0, BatteryStatus::Unknown);
+    for battery in system.iter_batteries() {
+        if let Some(capacity) = battery.capacity {
+            sum += capacity;
+            count += 1;
+        }
+        if status == BatteryStatus::Unknown || rank(battery.status) > rank(status) {
+            status = battery.status;
+        }
+    }
+    (count > 0).then(|| (sum / count as f64, status))
+}
+
+/// Map `value` in range `0..=full_scale` to `0.0..=1.0` logarithmically so
+/// that small values are still noticeable.
+fn log_scale(value: f64, full_scale: f64) -> f64 {
+    ((value.max(0.0) + 1.0).ln() / (full_scale + 1.0).ln()).min(1.0)
+}
+
 impl Default for State {
     fn default() -> Self {
         Self {
             mode_info: Default::default(),
-            sysinfo: sysinfo::current_system().expect("unsupported system"),
+            config: Config::default(),
+            sysinfo: sysinfo::current_system(sysinfo::Backend::Host).expect("unsupported system"),
             // Instantly start a new frame
             elapsed_since_last_frame_us: FRAME_INTERVAL_US,
             // Instantly perform the first measurement
             elapsed_since_last_measure_f: MEASURE_INTERVAL_F,
             cpus: None,
+            topology_stale: true,
+            swap_lamp: Lamp::default(),
+            activity_lamp: Lamp::default(),
+            battery_lamp: Lamp::default(),
+            disk_lamps: None,
+            node_lamps: None,
+            zellij_pid: 0,
+            pane_lamps: None,
+            pressure_high: false,
+            isolated_busy: false,
+            alert_lamp: Lamp::default(),
             last_timeout: Instant::now(),
             output_buffer: String::new(),
         }
     }
 }
 
+impl Lamp {
+    /// Set the fraction of frames on which this lamp lights up.
+    #[inline]
+    fn set_usage(&mut self, usage: f64) {
+        self.rate = (usage * u32::MAX as f64) as u32;
+    }
+
+    /// Get the fraction of frames on which this lamp lights up.
+    #[inline]
+    fn usage(&self) -> f64 {
+        self.rate as f64 / u32::MAX as f64
+    }
+
+    fn advance(&mut self, num_frames: u32) {
+        if num_frames > 0 {
+            (self.charge, self.lit) = self
+                .charge
+                .overflowing_add(self.rate.saturating_mul(num_frames));
+        }
+    }
+
+    /// Guess the number of frames until `self.lit` changes. Returns `None` if
+    /// it will never change.
+    fn next_change_f(&self) -> Option<u32> {
+        if self.lit {
+            // When will it stop overflowing?
+            let antirate = self.rate.wrapping_neg();
+            Some((self.charge / antirate).saturating_add(1))
+        } else {
+            // When will it overflow?
+            if self.rate == 0 {
+                // Never
+                return None;
+            }
+            let remaining_charge = self.charge.max(1).wrapping_neg();
+            // Rounding-up division
+            Some(div_ceil(remaining_charge, self.rate))
+        }
+    }
+}
+
+impl CpuState {
+    /// Get the color of this CPU's lamp for the current frame. Returns `None`
+    /// if it's unlit.
+    fn tint(&self, config: &Config) -> Option<Tint> {
+        if !self.online {
+            Some(Tint::Offline)
+        } else if self.unknown {
+            Some(Tint::Unknown)
+        } else if !self.lamp.lit && !self.accent.lit {
+            None
+        } else if !self.allowed && config.cpuset == CpusetMode::Dim {
+            Some(Tint::Excluded)
+        } else if self.heat.is_some() {
+            self.heat
+        } else if self.isolated && config.isolated_tint {
+            Some(Tint::Isolated)
+        } else if self.accent.lit {
+            Some(Tint::Accent)
+        } else if self.session.lit && config.session_overlay {
+            Some(Tint::Session)
+        } else if self.is_efficient() && config.core_types == CoreTypes::Color {
+            Some(Tint::Efficient)
+        } else {
+            Some(match self.freq {
+                Some(freq) if freq < 1.0 / 3.0 => Tint::Slow,
+                Some(freq) if freq > 2.0 / 3.0 => Tint::Fast,
+                _ => Tint::Normal,
+            })
+        }
+    }
+
+    /// Get the glyph of this CPU's lamp of color `tint` for the sparse mode.
+    fn glyph(&self, tint: Tint, config: &Config) -> char {
+        match tint {
+            Tint::Offline => '×',
+            Tint::Unknown => '·',
+            _ if self.is_efficient() && config.core_types == CoreTypes::Glyph => '◦',
+            _ => '•',
+        }
+    }
+
+    fn is_efficient(&self) -> bool {
+        self.topology.and_then(|t| t.core_type) == Some(sysinfo::CoreType::Efficiency)
+    }
+}
+
+impl Segment {
+    fn width(&self, rows: usize) -> usize {
+        match self {
+            Self::Gauge(..) | Self::Lamp(_) => 1,
+            Self::Lamps(lamps) => div_ceil(lamps.len(), rows.max(1)),
+            Self::Text(ref text, _) => text.chars().count(),
+        }
+    }
+
+    /// Paint the `row`-th row out of `rows` rows.
+    fn paint_row(&self, row: usize, rows: usize, painter: &mut Painter<'_>) {
+        match *self {
+            Self::Gauge(fraction, tint) => {
+                const BLOCKS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+                let filled = (fraction.max(0.0) * (rows * 8) as f64).round() as usize;
+                let level = filled.saturating_sub((rows - 1 - row) * 8).min(8);
+                painter.push(BLOCKS[level], tint);
+            }
+            Self::Lamp(Some(tint)) if row == rows - 1 => painter.push('•', tint),
+            Self::Lamp(_) => painter.push(' ', Tint::Normal),
+            Self::Lamps(ref lamps) => {
+                let width = self.width(rows);
+                for i in row * width..(row + 1) * width {
+                    match lamps.get(i).copied().flatten() {
+                        Some(tint) => painter.push('•', tint),
+                        None => painter.push(' ', Tint::Normal),
+                    }
+                }
+            }
+            Self::Text(ref text, tint) => {
+                for ch in text.chars() {
+                    painter.push(if row == rows - 1 { ch } else { ' ' }, tint);
+                }
+            }
+        }
+    }
+}
+
 impl State {
+    /// Get the mode and state of each alert.
+    fn alerts(&self) -> [(Alert, bool); 2] {
+        [
+            (self.config.pressure_alert, self.pressure_high),
+            (self.config.isolated_alert, self.isolated_busy),
+        ]
+    }
+
+    fn segments(&self) -> Vec<Segment> {
+        let mut segments = Vec::new();
+        if self.config.node_lamps {
+            let lamps = slist::iter(&self.node_lamps)
+                .map(|lamp| lamp.lit.then(|| Tint::Normal))
+                .collect();
+            segments.push(Segment::Lamps(lamps));
+        }
+        if self.config.memory_gauge {
+            if let Some(memory) = self.sysinfo.memory() {
+                segments.push(Segment::Gauge(memory.used_fraction(), Tint::Gauge));
+            }
+        }
+        if let (LoadSegment::Number | LoadSegment::Gauge, Some(load)) =
+            (self.config.load_segment, self.sysinfo.load())
+        {
+            let num_online_cpus = self.sysinfo.iter_cpu_info().filter(|c| c.online).count();
+            let load_per_cpu = load.avg[0] / num_online_cpus.max(1) as f64;
+            // Highlight an overcommitted system
+            let tint = if load_per_cpu > 1.0 {
+                Tint::Accent
+            } else {
+                Tint::Gauge
+            };
+            segments.push(if self.config.load_segment == LoadSegment::Gauge {
+                Segment::Gauge(load_per_cpu.min(1.0), tint)
+            } else {
+                Segment::Text(format_load(load.avg[0]), tint)
+            });
+        }
+        match (self.config.activity_segment, self.sysinfo.activity()) {
+            (ActivitySegment::Number, Some(activity)) => {
+                let text = format!(
+                    "cs{} in{} si{} fk{}",
+                    format_decimal(activity.context_switches),
+                    format_decimal(activity.interrupts),
+                    format_decimal(activity.softirqs),
+                    format_decimal(activity.forks)
+                );
+                segments.push(Segment::Text(text, Tint::Gauge));
+            }
+            (ActivitySegment::Lamp, _) => {
+                segments.push(Segment::Lamp(self.activity_lamp.lit.then(|| Tint::Warm)));
+            }
+            _ => {}
+        }
+        if self.config.process_segment {
+            if let Some(process) = self.sysinfo.top_processes(1).first() {
+                let text = format!("{} {:.0}%", process.name, process.usage * 100.0);
+                segments.push(Segment::Text(text, Tint::Gauge));
+            }
+        }
+        if self.config.swap_lamp {
+            segments.push(Segment::Lamp(self.swap_lamp.lit.then(|| Tint::Swap)));
+        }
+        if self.config.disk_lamps {
+            let lamps = slist::iter(&self.disk_lamps)
+                .map(|lamp| lamp.lit.then(|| Tint::Disk))
+                .collect();
+            segments.push(Segment::Lamps(lamps));
+        }
+        if let (PowerSegment::Package | PowerSegment::All, Some(power)) =
+            (self.config.power_segment, self.sysinfo.power())
+        {
+            let text = if self.config.power_segment == PowerSegment::All {
+                format!(
+                    "P{} C{} D{}",
+                    format_watts(power.package),
+                    format_watts(power.core),
+                    format_watts(power.dram)
+                )
+            } else {
+                format_watts(power.package)
+            };
+            segments.push(Segment::Text(text, Tint::Gauge));
+        }
+        if self.config.battery_segment {
+            if let Some((capacity, status)) = battery_summary(&*self.sysinfo) {
+                let sign = match status {
+                    BatteryStatus::Charging => "+",
+                    BatteryStatus::Discharging => "-",
+                    _ => "",
+                };
+                segments.push(Segment::Lamp(self.battery_lamp.lit.then(|| Tint::Accent)));
+                segments.push(Segment::Text(format!("{capacity:.0}%{sign}"), Tint::Gauge));
+            }
+        }
+        if self.config.net_segment != NetSegment::Hidden {
+            let interfaces = self
+                .sysinfo
+                .iter_network()
+                .filter(|i| self.config.net_filter.matches(i.name));
+            if self.config.net_segment == NetSegment::Total {
+                let mut total = sysinfo::NetRates::default();
+                for rates in interfaces.filter_map(|i| i.rates) {
+                    total += rates;
+                }
+                segments.push(Segment::Text(format_net_rates(&total), Tint::Gauge));
+            } else {
+                for interface in interfaces {
+                    let text = interface
+                        .rates
+                        .map_or_else(|| "↓?↑?".to_owned(), |rates| format_net_rates(&rates));
+                    segments.push(Segment::Text(
+                        format!("{}{text}", interface.name),
+                        Tint::Gauge,
+                    ));
+                }
+            }
+        }
+        segments
+    }
+
     fn on_timeout(&mut self) {
         let now = Instant::now();
         let elapsed_us = now
@@ -72,49 +535,280 @@ impl State {
             // Update CPUs
             match self.sysinfo.refresh_cpus() {
                 Ok(()) => {
-                    slist::resize_with(&mut self.cpus, self.sysinfo.num_cpus(), |_| CpuState {
-                        charge: 0,
-                        rate: 0,
-                        lit: false,
+                    slist::resize_with(&mut self.cpus, self.sysinfo.num_cpus(), |_| {
+                        CpuState::default()
                     });
-                    for (cpu, cpu_usage) in
-                        slist::iter_mut(&mut self.cpus).zip(self.sysinfo.iter_cpu_usage())
+                    for ((cpu, cpu_times), cpu_info) in slist::iter_mut(&mut self.cpus)
+                        .zip(self.sysinfo.iter_cpu_times())
+                        .zip(self.sysinfo.iter_cpu_info())
                     {
-                        cpu.rate = (cpu_usage * u32::MAX as f64) as u32;
+                        self.topology_stale |= cpu.online != cpu_info.online;
+                        cpu.online = cpu_info.online;
+                        cpu.allowed = cpu_info.allowed;
+                        cpu.isolated = cpu_info.isolated || cpu_info.nohz_full;
+                        cpu.unknown = cpu_times.is_none();
+                        let cpu_times = cpu_times.unwrap_or_default();
+                        cpu.lamp.set_usage(cpu_times.sum(self.config.lamp_times));
+                        cpu.accent
+                            .set_usage(cpu_times.sum(self.config.accent_times));
                     }
+                    let forks = self.sysinfo.activity().map_or(0.0, |a| a.forks);
+                    self.activity_lamp
+                        .set_usage(log_scale(forks, FORK_LAMP_FULL_SCALE));
+
+                    let threshold = self.config.isolated_threshold / 100.0;
+                    self.isolated_busy = slist::iter(&self.cpus)
+                        .any(|cpu| cpu.isolated && cpu.online && cpu.lamp.usage() > threshold);
                 }
                 Err(e) => {
                     eprintln!("Failed to update CPU statistics: {e:?}");
                 }
             }
+
+            let needs_topology = matches!(self.config.layout, Layout::Topology | Layout::Numa)
+                || self.config.core_types != CoreTypes::Same
+                || self.config.node_lamps
+                // Maps the core temperatures to CPUs
+                || self.config.temp_tint;
+            if needs_topology && self.topology_stale {
+                match self.sysinfo.refresh_topology() {
+                    Ok(()) => {
+                        self.topology_stale = false;
+                        let mut topology = self.sysinfo.iter_cpu_topology();
+                        for cpu in slist::iter_mut(&mut self.cpus) {
+                            cpu.topology = topology.next().flatten();
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to update CPU topology: {e:?}");
+                    }
+                }
+            }
+
+            if self.config.node_lamps {
+                // `(node ID, sum of usage, number of CPUs)`
+                let mut nodes: Vec<(u64, f64, usize)> = Vec::new();
+                for cpu in slist::iter(&self.cpus).filter(|cpu| cpu.online) {
+                    let node_id = match cpu.topology.and_then(|t| t.node_id) {
+                        Some(node_id) => node_id,
+                        None => continue,
+                    };
+                    let node = match nodes.iter_mut().find(|(id, ..)| *id == node_id) {
+                        Some(node) => node,
+                        None => {
+                            nodes.push((node_id, 0.0, 0));
+                            nodes.last_mut().unwrap()
+                        }
+                    };
+                    node.1 += cpu.lamp.usage();
+                    node.2 += 1;
+                }
+                nodes.sort_unstable_by_key(|&(id, ..)| id);
+                slist::resize_with(&mut self.node_lamps, nodes.len(), |_| Lamp::default());
+                for (lamp, (_, sum, count)) in slist::iter_mut(&mut self.node_lamps).zip(nodes) {
+                    lamp.set_usage(sum / count as f64);
+                }
+            }
+
+            if self.config.lamp_metric != LampMetric::Usage {
+                if let Err(e) = self.sysinfo.refresh_interrupts() {
+                    eprintln!("Failed to update interrupt statistics: {e:?}");
+                }
+                let mut interrupts = self.sysinfo.iter_cpu_interrupts();
+                for cpu in slist::iter_mut(&mut self.cpus) {
+                    let rate =
+                        interrupts
+                            .next()
+                            .flatten()
+                            .map(|rates| match self.config.lamp_metric {
+                                LampMetric::Softirq => rates.softirqs,
+                                _ => rates.irqs,
+                            });
+                    cpu.unknown = rate.is_none();
+                    cpu.lamp
+                        .set_usage(log_scale(rate.unwrap_or(0.0), IRQ_LAMP_FULL_SCALE));
+                }
+            }
+
+            if self.config.freq_intensity {
+                if let Err(e) = self.sysinfo.refresh_cpu_freq() {
+                    eprintln!("Failed to update CPU frequencies: {e:?}");
+                }
+                let mut freqs = self.sysinfo.iter_cpu_freq();
+                for cpu in slist::iter_mut(&mut self.cpus) {
+                    cpu.freq = freqs.next().flatten().map(|freq| freq.fraction());
+                }
+            }
+
+            if self.config.temp_tint {
+                if let Err(e) = self.sysinfo.refresh_thermal() {
+                    eprintln!("Failed to update temperatures: {e:?}");
+                }
+                let mut temps = self.sysinfo.iter_cpu_temp();
+                for cpu in slist::iter_mut(&mut self.cpus) {
+                    cpu.heat = match temps.next().flatten() {
+                        Some(temp) if temp >= self.config.temp_critical => Some(Tint::Hot),
+                        Some(temp) if temp >= self.config.temp_warn => Some(Tint::Warm),
+                        _ => None,
+                    };
+                }
+            }
+
+            if self.config.process_segment
+                || self.config.layout == Layout::Panes
+                || self.config.session_overlay
+            {
+                match self.sysinfo.refresh_processes() {
+                    Ok(()) => {
+                        let total: f64 = self.sysinfo.iter_cpu_usage().flatten().sum();
+                        let share = match self.sysinfo.session_usage() {
+                            Some(usage) if total > 0.0 => (usage / total).min(1.0),
+                            _ => 0.0,
+                        };
+                        for cpu in slist::iter_mut(&mut self.cpus) {
+                            cpu.session.set_usage(share);
+                        }
+
+                        let usages: Vec<f64> = self
+                            .sysinfo
+                            .iter_panes(self.zellij_pid)
+                            .map(|pane| pane.usage.min(1.0))
+                            .collect();
+                        slist::resize_with(&mut self.pane_lamps, usages.len(), |_| Lamp::default());
+                        for (lamp, usage) in slist::iter_mut(&mut self.pane_lamps).zip(usages) {
+                            lamp.set_usage(usage);
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to update process statistics: {e:?}");
+                    }
+                }
+            }
+
+            if self.config.memory_gauge {
+                if let Err(e) = self.sysinfo.refresh_memory() {
+                    eprintln!("Failed to update memory statistics: {e:?}");
+                }
+            }
+
+            if self.config.swap_lamp {
+                match self.sysinfo.refresh_swap() {
+                    Ok(()) => {
+                        let activity = self.sysinfo.swap().map_or(0.0, |swap| {
+                            swap.pages_in_rate.unwrap_or(0.0) + swap.pages_out_rate.unwrap_or(0.0)
+                        });
+                        self.swap_lamp
+                            .set_usage(log_scale(activity, SWAP_LAMP_FULL_SCALE));
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to update swap statistics: {e:?}");
+                    }
+                }
+            }
+
+            if self.config.disk_lamps {
+                match self.sysinfo.refresh_disks() {
+                    Ok(()) => {
+                        let config = &self.config;
+                        let usages: Vec<f64> = self
+                            .sysinfo
+                            .iter_disks()
+                            .filter(|disk| {
+                                (config.disk_partitions || !disk.partition)
+                                    && config.disk_filter.matches(disk.name)
+                            })
+                            .map(|disk| disk.rates.map_or(0.0, |rates| rates.utilization))
+                            .collect();
+                        slist::resize_with(&mut self.disk_lamps, usages.len(), |_| Lamp::default());
+                        for (lamp, usage) in slist::iter_mut(&mut self.disk_lamps).zip(usages) {
+                            lamp.set_usage(usage);
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to update disk statistics: {e:?}");
+                    }
+                }
+            }
+
+            if self.config.power_segment != PowerSegment::Hidden {
+                if let Err(e) = self.sysinfo.refresh_power() {
+                    eprintln!("Failed to update power statistics: {e:?}");
+                }
+            }
+
+            if self.config.battery_segment {
+                match self.sysinfo.refresh_batteries() {
+                    Ok(()) => {
+                        // Blinking is fastest at 0.5, where the lamp toggles on
+                        // every frame
+                        let usage = match battery_summary(&*self.sysinfo) {
+                            Some((capacity, BatteryStatus::Discharging)) => {
+                                (1.0 - capacity / 100.0).max(0.0).min(1.0) * 0.5
+                            }
+                            _ => 0.0,
+                        };
+                        self.battery_lamp.set_usage(usage);
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to update battery statistics: {e:?}");
+                    }
+                }
+            }
+
+            if self.config.net_segment != NetSegment::Hidden {
+                if let Err(e) = self.sysinfo.refresh_network() {
+                    eprintln!("Failed to update network statistics: {e:?}");
+                }
+            }
+
+            if self.config.pressure_alert != Alert::Off {
+                match self.sysinfo.refresh_pressure() {
+                    Ok(()) => {
+                        self.pressure_high = self.sysinfo.pressure().map_or(false, |pressure| {
+                            [pressure.cpu, pressure.memory, pressure.io]
+                                .iter()
+                                .flatten()
+                                .any(|p| p.some.avg10 > self.config.pressure_threshold)
+                        });
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to update pressure statistics: {e:?}");
+                    }
+                }
+            }
+
+            let blink = self.alerts().contains(&(Alert::Blink, true));
+            // Light up every other frame
+            self.alert_lamp.set_usage(if blink { 0.5 } else { 0.0 });
         }
 
         // The next timeout period
         let mut timeout_f = MEASURE_INTERVAL_F - self.elapsed_since_last_measure_f;
 
-        for cpu in slist::iter_mut(&mut self.cpus) {
-            if num_frames > 0 {
-                (cpu.charge, cpu.lit) = cpu
-                    .charge
-                    .overflowing_add(cpu.rate.saturating_mul(num_frames));
+        let cpu_lamps =
+            slist::iter_mut(&mut self.cpus).flat_map(|cpu| [&mut cpu.lamp, &mut cpu.accent]);
+        let lamps = cpu_lamps
+            .chain(slist::iter_mut(&mut self.disk_lamps))
+            .chain(slist::iter_mut(&mut self.node_lamps))
+            .chain(slist::iter_mut(&mut self.pane_lamps))
+            .chain([
+                &mut self.swap_lamp,
+                &mut self.activity_lamp,
+                &mut self.battery_lamp,
+                &mut self.alert_lamp,
+            ]);
+        for lamp in lamps {
+            lamp.advance(num_frames);
+            if let Some(change_f) = lamp.next_change_f() {
+                timeout_f = timeout_f.min(change_f);
+            }
+        }
+        // `session` only advances on the frames where `lamp` is lit
+        for cpu in slist::iter_mut(&mut self.cpus).filter(|cpu| cpu.lamp.lit) {
+            cpu.session.advance(num_frames);
+            if let Some(change_f) = cpu.session.next_change_f() {
+                timeout_f = timeout_f.min(change_f);
             }
-
-            // Guess the frame on which `cpu.lit` will change
-            let change_f = if cpu.lit {
-                // When will it stop overflowing?
-                let antirate = cpu.rate.wrapping_neg();
-                (cpu.charge / antirate).saturating_add(1)
-            } else {
-                // When will it overflow?
-                if cpu.rate == 0 {
-                    // Never
-                    continue;
-                }
-                let remaining_charge = cpu.charge.max(1).wrapping_neg();
-                // Rounding-up division
-                div_ceil(remaining_charge, cpu.rate)
-            };
-            timeout_f = timeout_f.min(change_f);
         }
 
         assert_ne!(timeout_f, 0);
@@ -127,6 +821,9 @@ impl State {
 impl ZellijPlugin for State {
     fn load(&mut self) {
         set_selectable(false);
+        self.config = Config::load();
+        self.sysinfo = sysinfo::current_system(self.config.backend).expect("unsupported system");
+        self.zellij_pid = get_plugin_ids().zellij_pid;
         subscribe(&[EventType::Timer, EventType::ModeUpdate]);
         self.last_timeout = Instant::now();
         self.on_timeout();
@@ -154,54 +851,383 @@ impl ZellijPlugin for State {
     }
 
     fn render(&mut self, rows: usize, cols: usize) {
+        let segments = self.segments();
+        let alert = self.alerts().iter().any(|&(mode, active)| match mode {
+            Alert::Off => false,
+            Alert::Tint => active,
+            Alert::Blink => active && self.alert_lamp.lit,
+        });
         let Self {
             cpus,
+            pane_lamps,
             mode_info,
+            config,
             output_buffer,
             ..
         } = self;
 
         output_buffer.clear();
 
-        let num_cpus = slist::iter(cpus).count();
-        let mut cpu_states = slist::iter(cpus).map(|c| c.lit);
-        let area = rows * cols;
-        if area >= num_cpus {
-            // Sparse (one cpu per cell)
-            for _ in 0..rows {
-                for _ in 0..cols {
-                    if cpu_states.next() == Some(true) {
-                        output_buffer.push_str("•");
-                    } else {
-                        output_buffer.push_str(" ");
-                    }
-                }
-                output_buffer.push_str("\n");
+        let colors = &mode_info.style.colors;
+        let bg = match colors.theme_hue {
+            ThemeHue::Light => colors.white,
+            ThemeHue::Dark => colors.black,
+        };
+        // Indexed by `Tint`
+        let styles = [
+            style!(colors.gray, bg),
+            style!(colors.gray, bg),
+            style!(colors.gray, bg).dimmed(),
+            style!(colors.orange, bg).dimmed(),
+            style!(colors.orange, bg),
+            style!(colors.orange, bg).bold(),
+            style!(colors.blue, bg),
+            style!(colors.green, bg),
+            style!(colors.red, bg),
+            style!(colors.pink, bg),
+            style!(colors.yellow, bg),
+            style!(colors.red, bg).bold(),
+            style!(colors.green, bg),
+            style!(colors.magenta, bg),
+            style!(colors.cyan, bg),
+        ];
+        let mut painter = Painter {
+            output_buffer,
+            styles: &styles,
+            alert_style: style!(bg, colors.red),
+            alert: false,
+            cur_tint: None,
+        };
+
+        // Each segment is preceded by a space
+        let segments_width: usize = segments.iter().map(|s| s.width(rows) + 1).sum();
+        let lamp_cols = cols.saturating_sub(segments_width);
+
+        let is_visible = |c: &&CpuState| c.allowed || config.cpuset != CpusetMode::Hide;
+        let visible_cpus: Vec<&CpuState> = slist::iter(cpus).filter(is_visible).collect();
+        let cells = match config.layout {
+            Layout::Sequential => sequential_cells(visible_cpus, rows, lamp_cols, config),
+            Layout::Topology => topology_or_sequential_cells(visible_cpus, rows, lamp_cols, config),
+            Layout::Numa => numa_cells(visible_cpus, rows, lamp_cols, config),
+            Layout::Panes => {
+                let lamps: Vec<_> = slist::iter(pane_lamps)
+                    .map(|lamp| lamp.lit.then(|| (Tint::Normal, '•')))
+                    .collect();
+                lamp_cells(&lamps, rows, lamp_cols)
             }
-        } else {
-            // Dense (8n cpus per cell)
-            let group_len = div_ceil(num_cpus, area * 8);
-            for _ in 0..rows {
-                for _ in 0..cols {
-                    let bitmap = (0..8).fold(0u8, |acc, bit| {
-                        let lit = (0..group_len)
-                            .map(|_| cpu_states.next().unwrap_or(false))
-                            .fold(false, |x, y| x | y);
-                        acc | ((lit as u8) << bit)
-                    });
-                    let braille = zellij_cpulamp::bitmap_to_braille(bitmap);
-                    output_buffer.push(braille);
+        };
+
+        for row in 0..rows {
+            if row > 0 {
+                painter.push('\n', Tint::Normal);
+            }
+            painter.set_alert(alert);
+            for col in 0..lamp_cols {
+                let (glyph, tint) = cells
+                    .get(row)
+                    .and_then(|cells| cells.get(col))
+                    .copied()
+                    .unwrap_or((' ', Tint::Normal));
+                painter.push(glyph, tint);
+            }
+            painter.set_alert(false);
+            for segment in segments.iter() {
+                painter.push(' ', Tint::Normal);
+                segment.paint_row(row, rows, &mut painter);
+            }
+        }
+
+        write!(output_buffer, "{}", styles[Tint::Normal as usize].suffix()).unwrap();
+        print!("{output_buffer}");
+    }
+}
+
+/// Arrange `cpus` in `rows` × `cols` cells for [`Layout::Sequential`], giving
+/// performance and efficiency cores separate bands of rows if
+/// [`Config::core_types`] is [`CoreTypes::Rows`]. Returns the glyph and tint of
+/// each cell, indexed by `[row][column]`.
+fn sequential_cells(
+    mut cpus: Vec<&CpuState>,
+    rows: usize,
+    cols: usize,
+    config: &Config,
+) -> Vec<Vec<(char, Tint)>> {
+    let lamps = |cpus: &[&CpuState]| -> Vec<_> {
+        cpus.iter()
+            .map(|c| c.tint(config).map(|tint| (tint, c.glyph(tint, config))))
+            .collect()
+    };
+    if config.core_types == CoreTypes::Rows {
+        // Stable, so CPU IDs stay in order within each band
+        cpus.sort_by_key(|c| c.is_efficient());
+        let num_efficient = cpus.iter().filter(|c| c.is_efficient()).count();
+        let num_performance = cpus.len() - num_efficient;
+        if rows >= 2 && num_efficient > 0 && num_performance > 0 {
+            let efficient_rows = (rows * num_efficient / cpus.len()).clamp(1, rows - 1);
+            let efficient_cpus = cpus.split_off(num_performance);
+            let mut cells = lamp_cells(&lamps(&cpus), rows - efficient_rows, cols);
+            cells.extend(lamp_cells(&lamps(&efficient_cpus), efficient_rows, cols));
+            return cells;
+        }
+    }
+    lamp_cells(&lamps(&cpus), rows, cols)
+}
+
+/// Place `lamps` (the tint and glyph of each lamp, or `None` if it's unlit) in
+/// `rows` × `cols` cells row by row, packing several lamps into a Braille cell
+/// if they don't fit.
+fn lamp_cells(lamps: &[Option<(Tint, char)>], rows: usize, cols: usize) -> Vec<Vec<(char, Tint)>> {
+    let num_lamps = lamps.len();
+    let mut lamps = lamps.iter().copied();
+    let area = rows * cols;
+    // Dense mode packs 8n lamps per cell
+    let dense_group_len = (area < num_lamps).then(|| div_ceil(num_lamps, area.max(1) * 8));
+    let mut cells = vec![Vec::new(); rows];
+    for row in cells.iter_mut() {
+        for _ in 0..cols {
+            row.push(if let Some(group_len) = dense_group_len {
+                let mut cell_tint = None;
+                let bitmap = (0..8).fold(0u8, |acc, bit| {
+                    // Offline CPUs and those of unknown usage aren't lit
+                    let tint = (0..group_len)
+                        .map(|_| lamps.next().flatten().map(|(tint, _)| tint))
+                        .filter(|tint| !matches!(tint, Some(Tint::Offline | Tint::Unknown)))
+                        .fold(None, Option::max);
+                    cell_tint = cell_tint.max(tint);
+                    acc | ((tint.is_some() as u8) << bit)
+                });
+                let braille = zellij_cpulamp::bitmap_to_braille(bitmap);
+                (braille, cell_tint.unwrap_or(Tint::Normal))
+            } else if let Some((tint, glyph)) = lamps.next().flatten() {
+                (glyph, tint)
+            } else {
+                (' ', Tint::Normal)
+            });
+        }
+    }
+    cells
+}
+
+/// Arrange `cpus` in `rows` × `cols` cells for [`Layout::Numa`]. Each NUMA
+/// node gets a band of rows, or a block of columns separated by a blank column
+/// if there are more nodes than rows. CPUs of unknown nodes come last.
+fn numa_cells(
+    cpus: Vec<&CpuState>,
+    rows: usize,
+    cols: usize,
+    config: &Config,
+) -> Vec<Vec<(char, Tint)>> {
+    let mut nodes: Vec<(Option<u64>, Vec<&CpuState>)> = Vec::new();
+    for cpu in cpus {
+        let node_id = cpu.topology.and_then(|t| t.node_id);
+        match nodes.iter_mut().find(|(id, _)| *id == node_id) {
+            Some((_, node_cpus)) => node_cpus.push(cpu),
+            None => nodes.push((node_id, vec![cpu])),
+        }
+    }
+    nodes.sort_by_key(|&(id, _)| (id.is_none(), id));
+
+    let num_nodes = nodes.len().max(1);
+    let mut cells = Vec::new();
+    if num_nodes <= rows {
+        for (i, (_, node_cpus)) in nodes.into_iter().enumerate() {
+            let node_rows = rows / num_nodes + (i < rows % num_nodes) as usize;
+            cells.extend(sequential_cells(node_cpus, node_rows, cols, config));
+        }
+    } else {
+        cells.resize(rows, Vec::new());
+        let width = ((cols + 1) / num_nodes).saturating_sub(1);
+        for (i, (_, node_cpus)) in nodes.into_iter().enumerate() {
+            let block = sequential_cells(node_cpus, rows, width, config);
+            for (row, block_row) in cells.iter_mut().zip(block) {
+                if i > 0 {
+                    row.push((' ', Tint::Normal));
                 }
-                output_buffer.push_str("\n");
+                row.extend(block_row);
+            }
+        }
+    }
+    cells
+}
+
+/// Arrange `cpus` in `rows` × `cols` cells for [`Layout::Topology`], or as in
+/// [`Layout::Sequential`] if a CPU's topology is unknown or the cores don't fit,
+/// so that no CPU is left out.
+fn topology_or_sequential_cells(
+    cpus: Vec<&CpuState>,
+    rows: usize,
+    cols: usize,
+    config: &Config,
+) -> Vec<Vec<(char, Tint)>> {
+    if cpus.iter().any(|c| c.topology.is_none()) {
+        return sequential_cells(cpus, rows, cols, config);
+    }
+    let lamps = cpus
+        .iter()
+        .filter_map(|c| Some((c.topology?, c.tint(config))));
+    let cells = topology_cells(lamps, rows, config.core_types == CoreTypes::Rows);
+    if cells.iter().any(|row| row.len() > cols) {
+        sequential_cells(cpus, rows, cols, config)
+    } else {
+        cells
+    }
+}
+
+/// Arrange CPUs for [`Layout::Topology`]. Each cell holds the SMT siblings of
+/// a core. The cores of each package are placed in a block, row by row, and the
+/// blocks are separated by a blank column. If `split_core_types` is set, the
+/// efficiency cores of each package are placed in a separate block. Returns
+/// the glyph and tint of each cell, indexed by `[row][column]`.
+fn topology_cells(
+    cpus: impl Iterator<Item = (sysinfo::CpuTopology, Option<Tint>)>,
+    rows: usize,
+    split_core_types: bool,
+) -> Vec<Vec<(char, Tint)>> {
+    // `(topology of the first thread, bitmap, number of threads, tint)`
+    let mut cores: Vec<(sysinfo::CpuTopology, u8, u32, Option<Tint>)> = Vec::new();
+    for (topology, tint) in cpus {
+        let core = match cores
+            .iter_mut()
+            .find(|(t, ..)| t.first_sibling == topology.first_sibling)
+        {
+            Some(core) => core,
+            None => {
+                cores.push((topology, 0, 0, None));
+                cores.last_mut().unwrap()
             }
+        };
+        // A Braille cell can't hold more than 8 threads
+        if core.2 < 8 {
+            core.1 |= (tint.is_some() as u8) << core.2;
+        }
+        core.2 += 1;
+        core.3 = core.3.max(tint);
+    }
+    let block_key = |t: &sysinfo::CpuTopology| {
+        let efficient = t.core_type == Some(sysinfo::CoreType::Efficiency);
+        (t.package_id, split_core_types && efficient)
+    };
+    cores.sort_unstable_by_key(|(t, ..)| (block_key(t), t.cluster_id, t.core_id, t.first_sibling));
+
+    let rows = rows.max(1);
+    let mut cells = vec![Vec::new(); rows];
+    let mut block_start = 0;
+    let mut cores = &cores[..];
+    while let Some((first, ..)) = cores.first() {
+        let len = cores
+            .iter()
+            .take_while(|(t, ..)| block_key(t) == block_key(first))
+            .count();
+        let width = div_ceil(len, rows);
+        for (i, &(_, bitmap, _, tint)) in cores[..len].iter().enumerate() {
+            let (row, col) = (i / width, block_start + i % width);
+            cells[row].resize(col + 1, (' ', Tint::Normal));
+            cells[row][col] = (
+                zellij_cpulamp::bitmap_to_braille(bitmap),
+                tint.unwrap_or(Tint::Normal),
+            );
+        }
+        block_start += width + 1;
+        cores = &cores[len..];
+    }
+    cells
+}
+
+/// Writes styled characters to a buffer, emitting escape sequences only when
+/// the style changes
+struct Painter<'a> {
+    output_buffer: &'a mut String,
+    /// Indexed by [`Tint`]
+    styles: &'a [ansi_term::Style],
+    /// Overrides `styles` while `alert` is set
+    alert_style: ansi_term::Style,
+    alert: bool,
+    cur_tint: Option<Tint>,
+}
+
+impl Painter<'_> {
+    fn set_alert(&mut self, alert: bool) {
+        if self.alert != alert {
+            self.alert = alert;
+            self.cur_tint = None;
         }
+    }
+
+    fn push(&mut self, ch: char, tint: Tint) {
+        if self.cur_tint != Some(tint) {
+            self.cur_tint = Some(tint);
+            let style = if self.alert {
+                self.alert_style
+            } else {
+                self.styles[tint as usize]
+            };
+            write!(self.output_buffer, "{}", style.prefix()).unwrap();
+        }
+        self.output_buffer.push(ch);
+    }
+}
 
-        output_buffer.pop();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let (bg, fg) = match mode_info.style.colors.theme_hue {
-            ThemeHue::Light => (mode_info.style.colors.white, mode_info.style.colors.orange),
-            ThemeHue::Dark => (mode_info.style.colors.black, mode_info.style.colors.orange),
+    fn cpu(id: usize, topology: bool) -> CpuState {
+        let mut cpu = CpuState {
+            online: true,
+            allowed: true,
+            topology: topology.then(|| sysinfo::CpuTopology {
+                core_id: id as u64,
+                first_sibling: id,
+                ..Default::default()
+            }),
+            ..Default::default()
         };
-        print!("{}", style!(fg, bg).paint(output_buffer.as_str()));
+        cpu.lamp.lit = true;
+        cpu
+    }
+
+    #[test]
+    fn dense_lamps() {
+        let lamps = [
+            Some((Tint::Offline, '×')),
+            Some((Tint::Unknown, '·')),
+            Some((Tint::Slow, '•')),
+            None,
+            Some((Tint::Offline, '×')),
+        ];
+        // A Braille cell of 8 lamps and one of only offline and unknown CPUs
+        let cells = lamp_cells(&[&lamps[..], &[None; 3], &lamps[..2]].concat(), 1, 2);
+        assert_eq!(
+            cells,
+            [[
+                (zellij_cpulamp::bitmap_to_braille(0b100), Tint::Slow),
+                (zellij_cpulamp::bitmap_to_braille(0), Tint::Normal),
+            ]]
+        );
+    }
+
+    #[test]
+    fn topology_fallback() {
+        let config = Config::default();
+        let glyphs = |cpus: &[CpuState], cols| -> Vec<String> {
+            topology_or_sequential_cells(cpus.iter().collect(), 1, cols, &config)
+                .iter()
+                .map(|row| row.iter().map(|&(glyph, _)| glyph).collect())
+                .collect()
+        };
+
+        let cpus: Vec<_> = (0..3).map(|id| cpu(id, true)).collect();
+        let core = zellij_cpulamp::bitmap_to_braille(1).to_string();
+        assert_eq!(glyphs(&cpus, 3), [core.repeat(3)]);
+        // The cores don't fit
+        let sequential = sequential_cells(cpus.iter().collect(), 1, 2, &config);
+        assert_eq!(
+            topology_or_sequential_cells(cpus.iter().collect(), 1, 2, &config),
+            sequential
+        );
+
+        // CPU 1's topology is unknown
+        let cpus = [cpu(0, true), cpu(1, false), cpu(2, true)];
+        assert_eq!(glyphs(&cpus, 3), ["•••"]);
     }
 }
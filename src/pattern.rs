@@ -0,0 +1,80 @@
+//! Name patterns for selecting devices
+
+/// Check if `name` matches `pattern`, where `*` matches any sequence of
+/// characters and `?` matches any single character.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name) = (pattern.as_bytes(), name.as_bytes());
+    // The position to resume from after the last `*`
+    let mut backtrack = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                // Let the last `*` consume one more character
+                Some((star_p, star_n)) => {
+                    backtrack = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Selects names matching any of `include` and none of `exclude`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl NameFilter {
+    pub fn new(include: &[&str], exclude: &[&str]) -> Self {
+        let to_vec = |patterns: &[&str]| patterns.iter().map(|&p| p.to_owned()).collect();
+        Self {
+            include: to_vec(include),
+            exclude: to_vec(exclude),
+        }
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        self.include.iter().any(|p| glob_match(p, name))
+            && !self.exclude.iter().any(|p| glob_match(p, name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob() {
+        assert!(glob_match("eth0", "eth0"));
+        assert!(!glob_match("eth0", "eth01"));
+        assert!(glob_match("veth*", "veth1a2b"));
+        assert!(glob_match("veth*", "veth"));
+        assert!(!glob_match("veth*", "eth0"));
+        assert!(glob_match("*", ""));
+        assert!(glob_match("e*h?", "ethereth0"));
+        assert!(!glob_match("e*h?", "ethereth"));
+        assert!(glob_match("*a*b", "xaxxab"));
+    }
+
+    #[test]
+    fn filter() {
+        let filter = NameFilter::new(&["*"], &["lo", "veth*"]);
+        assert!(filter.matches("eth0"));
+        assert!(!filter.matches("lo"));
+        assert!(!filter.matches("veth0"));
+    }
+}
@@ -5,12 +5,31 @@ use std::{
     fs::File,
     io::prelude::*,
     path::PathBuf,
-    sync::Mutex,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
     time::{Duration, Instant},
 };
 use zellij_tile::prelude::*;
 
+/// Run `cmd`, which is expected to start one external program (i.e., fork
+/// once), and get its output.
 pub fn spawn_and_get_output(cmd: &[u8]) -> Vec<u8> {
+    NUM_FORKS.fetch_add(1, Ordering::Relaxed);
+    run(cmd)
+}
+
+/// Get the number of processes started by [`spawn_and_get_output`] and
+/// [`read_files`] so far, so that they can be told apart from the system's.
+pub fn num_forks() -> u64 {
+    NUM_FORKS.load(Ordering::Relaxed)
+}
+
+static NUM_FORKS: AtomicU64 = AtomicU64::new(0);
+
+/// Run `cmd` in the proxy and get its output.
+fn run(cmd: &[u8]) -> Vec<u8> {
     let proxy = PROXY.get_or_init(|| Mutex::new(Proxy::new()));
     let mut proxy = proxy.lock().unwrap();
     retry_until_success(
@@ -26,12 +45,79 @@ pub fn spawn_and_get_output(cmd: &[u8]) -> Vec<u8> {
     .unwrap_or_else(|ProxyExitedError| panic!("proxy exited and failed to recover"))
 }
 
+/// Get the PID of the shell that runs the commands of
+/// [`spawn_and_get_output`]. Its parent is the Zellij server.
+pub fn proxy_pid() -> u32 {
+    let proxy = PROXY.get_or_init(|| Mutex::new(Proxy::new()));
+    proxy.lock().unwrap().pid
+}
+
+/// Read the files matching the given shell glob patterns.
+pub fn read_files(patterns: &[&str]) -> Files {
+    let mut cmd = b"for f in".to_vec();
+    for pattern in patterns {
+        cmd.push(b' ');
+        cmd.extend_from_slice(pattern.as_bytes());
+    }
+    // Delimit paths and contents by NUL. Mark failed reads with SOH.
+    cmd.extend_from_slice(
+        br#"; do printf '\0%s\0' "$f"; cat "$f" 2> /dev/null || printf '\1'; done"#,
+    );
+    let files = Files(run(&cmd));
+    // One `cat` per file
+    NUM_FORKS.fetch_add(files.iter().count() as u64, Ordering::Relaxed);
+    files
+}
+
+/// Quote `word` so that the shell takes it literally.
+pub fn shell_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', r"'\''"))
+}
+
+/// The output of [`read_files`]
+pub struct Files(Vec<u8>);
+
+impl Files {
+    /// Construct `Self` from pairs of paths and contents.
+    #[cfg(test)]
+    pub fn from_entries(entries: &[(&str, &str)]) -> Self {
+        let mut buf = Vec::new();
+        for (path, content) in entries {
+            buf.push(0);
+            buf.extend_from_slice(path.as_bytes());
+            buf.push(0);
+            buf.extend_from_slice(content.as_bytes());
+        }
+        Self(buf)
+    }
+
+    /// Iterate over the paths and contents of the read files. The content is
+    /// `None` if the file couldn't be read.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Option<&str>)> + Clone + '_ {
+        let mut parts = self.0.split(|&b| b == 0).skip(1);
+        std::iter::from_fn(move || {
+            let path = parts.next()?;
+            let content = parts.next()?;
+            let content = Some(content)
+                .filter(|content| content.last() != Some(&1))
+                .and_then(|content| std::str::from_utf8(content).ok());
+            Some((std::str::from_utf8(path).unwrap_or(""), content))
+        })
+    }
+
+    /// Get the contents of the file at `path`.
+    pub fn get(&self, path: &str) -> Option<&str> {
+        self.iter().find(|&(p, _)| p == path)?.1
+    }
+}
+
 static PROXY: OnceCell<Mutex<Proxy>> = OnceCell::new();
 
 struct Proxy {
     pipe_req: File,
     pipe_res: File,
     pipe_buf_path: PathBuf,
+    pid: u32,
 }
 
 /// The shell command to start [`PROXY_SCRIPT`].
@@ -106,7 +192,7 @@ impl Proxy {
             PROXY_LOADER,
             "subproc",
             &script_name_tmp,
-            &script_name,
+            script_name,
             &pipe_name,
         ]);
 
@@ -123,9 +209,14 @@ impl Proxy {
             pipe_req,
             pipe_res,
             pipe_buf_path,
+            pid: 0,
         };
 
-        this.spawn_and_get_output(b"echo test").unwrap();
+        let pid = this.spawn_and_get_output(b"echo $$").unwrap();
+        this.pid = std::str::from_utf8(&pid)
+            .ok()
+            .and_then(|pid| pid.trim().parse().ok())
+            .unwrap_or_else(|| panic!("unexpected output from a subprocess proxy: {pid:?}"));
 
         this
     }
@@ -0,0 +1,135 @@
+//! The `ctxt`, `intr`, `softirq`, and `processes` counters in `/proc/stat`
+use anyhow::{Context, Result};
+use std::time::Instant;
+
+use crate::sysinfo::ActivityRates;
+
+#[derive(Debug, Default)]
+pub struct Activity {
+    /// The time and counters of the last sample
+    last_counters: Option<(Instant, Counters)>,
+    pub rates: Option<ActivityRates>,
+}
+
+/// `[ctxt, intr, softirq, processes]`
+type Counters = [u64; 4];
+
+impl Activity {
+    /// Take a sample from `/proc/stat`. `own_forks` is the number of processes
+    /// this plugin has started so far, which don't count toward
+    /// [`ActivityRates::forks`].
+    pub fn refresh(&mut self, stat: &str, own_forks: u64, now: Instant) -> Result<()> {
+        let counters = parse(stat)?.map(|mut counters| {
+            counters[3] = counters[3].saturating_sub(own_forks);
+            counters
+        });
+        self.rates = match (counters, self.last_counters) {
+            (Some(counters), Some((last_time, last_counters))) => {
+                let elapsed = now.saturating_duration_since(last_time).as_secs_f64();
+                rates(&counters, &last_counters, elapsed)
+            }
+            _ => None,
+        };
+        self.last_counters = counters.map(|counters| (now, counters));
+        Ok(())
+    }
+}
+
+/// Returns `None` if no time has elapsed or the counters went backwards.
+fn rates(counters: &Counters, last_counters: &Counters, elapsed: f64) -> Option<ActivityRates> {
+    if elapsed <= 0.0 {
+        return None;
+    }
+    let rate = |i: usize| Some(counters[i].checked_sub(last_counters[i])? as f64 / elapsed);
+    Some(ActivityRates {
+        context_switches: rate(0)?,
+        interrupts: rate(1)?,
+        softirqs: rate(2)?,
+        forks: rate(3)?,
+    })
+}
+
+/// Get the counters from `/proc/stat`. Returns `None` if any of them is absent.
+fn parse(stat: &str) -> Result<Option<Counters>> {
+    let mut counters = [None; 4];
+    for line in stat.lines() {
+        let (key, values) = match line.split_once(' ') {
+            Some(x) => x,
+            None => continue,
+        };
+        let counter = match key {
+            "ctxt" => &mut counters[0],
+            "intr" => &mut counters[1],
+            "softirq" => &mut counters[2],
+            "processes" => &mut counters[3],
+            _ => continue,
+        };
+        // `intr` and `softirq` are followed by the per-source counts
+        let total = values.split_ascii_whitespace().next().unwrap_or("");
+        *counter = Some(
+            total
+                .parse()
+                .with_context(|| format!("failed to parse line '{line}'"))?,
+        );
+    }
+    Ok(match counters {
+        [Some(ctxt), Some(intr), Some(softirq), Some(processes)] => {
+            Some([ctxt, intr, softirq, processes])
+        }
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn refresh() {
+        let stat = |ctxt, intr, processes| {
+            format!(
+                "cpu0 1 2 3 4\n\
+                intr {intr} 27 9 0 0\n\
+                ctxt {ctxt}\n\
+                btime 1659830400\n\
+                processes {processes}\n\
+                softirq 1000 2 998\n"
+            )
+        };
+        let mut activity = Activity::default();
+        let t0 = Instant::now();
+        activity.refresh(&stat(5000, 300, 40), 10, t0).unwrap();
+        assert_eq!(activity.rates, None);
+
+        // 4 of the 10 forks were ours
+        activity
+            .refresh(&stat(9000, 500, 50), 14, t0 + Duration::from_secs(2))
+            .unwrap();
+        assert_eq!(
+            activity.rates,
+            Some(ActivityRates {
+                context_switches: 2000.0,
+                interrupts: 100.0,
+                softirqs: 0.0,
+                forks: 3.0,
+            })
+        );
+
+        // The counters went backwards
+        activity
+            .refresh(&stat(8000, 600, 60), 14, t0 + Duration::from_secs(3))
+            .unwrap();
+        assert_eq!(activity.rates, None);
+    }
+
+    #[test]
+    fn parse_counters() {
+        assert_eq!(
+            parse("ctxt 1\nintr 2 0 0\nsoftirq 3\nprocesses 4\n").unwrap(),
+            Some([1, 2, 3, 4])
+        );
+        assert_eq!(parse("cpu0 1 2 3 4\nctxt 1\n").unwrap(), None);
+        assert!(parse("ctxt many\n").is_err());
+    }
+}
@@ -0,0 +1,450 @@
+//! CPU usage of the cgroup (v2) containing Zellij
+use anyhow::{Context, Result};
+use std::time::Instant;
+
+use super::{cpulist::CpuSet, CPU_FILES, SYS_CPU_ONLINE};
+use crate::{
+    iter::BoxMiniIterator,
+    process::{self, Files},
+    sysinfo::{
+        ActivityRates, BatteryInfo, CpuInfo, CpuTime, CpuTimes, DiskInfo, LoadInfo, MemoryInfo,
+        NetInterface, PowerInfo, PressureInfo, ProcessInfo, Sensor, SwapInfo,
+    },
+};
+
+/// Commands run by [`process`] belong to the same cgroup as Zellij.
+const PROC_SELF_CGROUP: &str = "/proc/self/cgroup";
+const SYS_FS_CGROUP: &str = "/sys/fs/cgroup";
+
+/// Reports the CPU usage of the cgroup as a fraction of its CPU quota through
+/// `ceil(quota / period)` virtual CPUs, but no more than the CPUs in its cpuset,
+/// or as many virtual CPUs as the CPUs in its cpuset if there's no quota. Everything else comes from the host-wide
+/// [`super::System`].
+#[derive(Debug, Default)]
+pub struct CgroupSystem {
+    host: super::System,
+    /// The cgroup's directory, e.g., `/sys/fs/cgroup/user.slice`. Resolved on
+    /// the first refresh.
+    dir: Option<String>,
+    /// The time and value of the last sample
+    last_stat: Option<(Instant, CpuStat)>,
+    /// The number of CPUs' worth of time available to the cgroup
+    quota: f64,
+    /// The usage during the last refresh interval as a fraction of `quota`.
+    /// `None` if it's unknown.
+    usage: Option<f64>,
+    /// Ditto, broken down by [`CpuTime`]
+    times: Option<CpuTimes>,
+}
+
+/// The counters in `cpu.stat`, measured in microseconds
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct CpuStat {
+    usage: u64,
+    user: u64,
+    system: u64,
+}
+
+impl CgroupSystem {
+    fn refresh_cpus_from(&mut self, dir: &str, files: &Files, now: Instant) -> Result<()> {
+        let path = format!("{dir}/cpu.stat");
+        let stat = files
+            .get(&path)
+            .with_context(|| format!("failed to read '{path}'"))?;
+        let stat = parse_cpu_stat(stat).with_context(|| format!("failed to parse '{path}'"))?;
+
+        // The effective quota is the tightest one among the ancestors
+        let mut quota = None;
+        for dir in ancestors(dir) {
+            let path = format!("{dir}/cpu.max");
+            // `None` if the `cpu` controller isn't enabled
+            let cpu_max = match files.get(&path) {
+                Some(x) => x,
+                None => continue,
+            };
+            if let Some(x) =
+                parse_cpu_max(cpu_max).with_context(|| format!("failed to parse '{path}'"))?
+            {
+                quota = Some(quota.map_or(x, |quota: f64| quota.min(x)));
+            }
+        }
+        // The cgroup can't use more CPUs than its cpuset has, nor fewer than
+        // that without a quota
+        let path = format!("{dir}/cpuset.cpus.effective");
+        let cpus = match files.get(&path).or_else(|| files.get(SYS_CPU_ONLINE)) {
+            Some(cpus) => Some(CpuSet::parse(cpus)?.count() as f64),
+            None => None,
+        };
+        self.quota = match (quota, cpus) {
+            (Some(quota), Some(cpus)) => quota.min(cpus),
+            (Some(quota), None) => quota,
+            (None, Some(cpus)) => cpus,
+            (None, None) => anyhow::bail!("no CPU quota, and failed to read the CPU list"),
+        };
+
+        let elapsed = self
+            .last_stat
+            .map(|(last_time, _)| now.saturating_duration_since(last_time).as_secs_f64())
+            .filter(|&elapsed| elapsed > 0.0);
+        let last_stat = self.last_stat.map(|(_, stat)| stat);
+        let available_usec = elapsed.map(|elapsed| elapsed * 1.0e6 * self.quota);
+        let fraction = |new: u64, old: u64| -> Option<f64> {
+            Some(new.checked_sub(old)? as f64 / available_usec?)
+        };
+        self.usage = last_stat.and_then(|last| fraction(stat.usage, last.usage));
+        self.times = last_stat.and_then(|last| {
+            let mut times = CpuTimes::default();
+            times.0[CpuTime::User as usize] = fraction(stat.user, last.user)?;
+            times.0[CpuTime::System as usize] = fraction(stat.system, last.system)?;
+            Some(times)
+        });
+        self.last_stat = Some((now, stat));
+
+        Ok(())
+    }
+}
+
+impl crate::sysinfo::System for CgroupSystem {
+    fn refresh_cpus(&mut self) -> Result<()> {
+        let dir = match self.dir {
+            Some(ref dir) => dir.clone(),
+            None => {
+                let files = process::read_files(&[PROC_SELF_CGROUP]);
+                let cgroup = files
+                    .get(PROC_SELF_CGROUP)
+                    .context("failed to read /proc/self/cgroup")?;
+                let dir = format!("{SYS_FS_CGROUP}{}", parse_cgroup_path(cgroup)?);
+                self.dir.insert(dir).clone()
+            }
+        };
+
+        let mut paths = vec![
+            format!("{dir}/cpu.stat"),
+            format!("{dir}/cpuset.cpus.effective"),
+        ];
+        // Includes `SYS_CPU_ONLINE`
+        paths.extend(CPU_FILES.iter().map(|&path| path.to_owned()));
+        paths.extend(ancestors(&dir).map(|dir| format!("{dir}/cpu.max")));
+        // Cgroup names may contain characters special to the shell
+        let patterns: Vec<String> = paths
+            .iter()
+            .map(|path| process::shell_quote(path))
+            .collect();
+        let patterns: Vec<&str> = patterns.iter().map(String::as_str).collect();
+        let files = process::read_files(&patterns);
+
+        let now = Instant::now();
+        self.refresh_cpus_from(&dir, &files, now)?;
+        // For `load` and `activity`
+        self.host.refresh_cpus_from(&files, now)
+    }
+
+    fn num_cpus(&self) -> usize {
+        (self.quota.ceil() as usize).max(1)
+    }
+
+    fn iter_cpu_usage(&self) -> BoxMiniIterator<'_, Option<f64>> {
+        Box::new(std::iter::repeat(self.usage).take(self.num_cpus()))
+    }
+
+    fn iter_cpu_times(&self) -> BoxMiniIterator<'_, Option<CpuTimes>> {
+        Box::new(std::iter::repeat(self.times).take(self.num_cpus()))
+    }
+
+    fn iter_cpu_info(&self) -> BoxMiniIterator<'_, CpuInfo> {
+        Box::new((0..self.num_cpus()).map(|id| CpuInfo {
+            id,
+            online: true,
+            allowed: true,
+            isolated: false,
+            nohz_full: false,
+        }))
+    }
+
+    fn load(&self) -> Option<LoadInfo> {
+        self.host.load()
+    }
+
+    fn activity(&self) -> Option<ActivityRates> {
+        self.host.activity()
+    }
+
+    fn refresh_memory(&mut self) -> Result<()> {
+        self.host.refresh_memory()
+    }
+
+    fn memory(&self) -> Option<MemoryInfo> {
+        self.host.memory()
+    }
+
+    fn refresh_swap(&mut self) -> Result<()> {
+        self.host.refresh_swap()
+    }
+
+    fn swap(&self) -> Option<SwapInfo> {
+        self.host.swap()
+    }
+
+    fn refresh_pressure(&mut self) -> Result<()> {
+        self.host.refresh_pressure()
+    }
+
+    fn pressure(&self) -> Option<PressureInfo> {
+        self.host.pressure()
+    }
+
+    fn refresh_power(&mut self) -> Result<()> {
+        self.host.refresh_power()
+    }
+
+    fn power(&self) -> Option<PowerInfo> {
+        self.host.power()
+    }
+
+    fn refresh_batteries(&mut self) -> Result<()> {
+        self.host.refresh_batteries()
+    }
+
+    fn iter_batteries(&self) -> BoxMiniIterator<'_, BatteryInfo<'_>> {
+        self.host.iter_batteries()
+    }
+
+    fn refresh_network(&mut self) -> Result<()> {
+        self.host.refresh_network()
+    }
+
+    fn iter_network(&self) -> BoxMiniIterator<'_, NetInterface<'_>> {
+        self.host.iter_network()
+    }
+
+    fn refresh_thermal(&mut self) -> Result<()> {
+        self.host.refresh_thermal()
+    }
+
+    fn iter_sensors(&self) -> BoxMiniIterator<'_, Sensor<'_>> {
+        self.host.iter_sensors()
+    }
+
+    fn refresh_processes(&mut self) -> Result<()> {
+        self.host.refresh_processes()
+    }
+
+    fn top_processes(&self, count: usize) -> Vec<ProcessInfo<'_>> {
+        self.host.top_processes(count)
+    }
+
+    fn iter_panes(&self, server_pid: u32) -> BoxMiniIterator<'_, ProcessInfo<'_>> {
+        self.host.iter_panes(server_pid)
+    }
+
+    fn session_usage(&self) -> Option<f64> {
+        self.host.session_usage()
+    }
+
+    fn refresh_disks(&mut self) -> Result<()> {
+        self.host.refresh_disks()
+    }
+
+    fn iter_disks(&self) -> BoxMiniIterator<'_, DiskInfo<'_>> {
+        self.host.iter_disks()
+    }
+}
+
+/// Get the cgroup v2 path (e.g., `/user.slice`) from `/proc/self/cgroup`.
+/// The root cgroup is represented by an empty string.
+fn parse_cgroup_path(cgroup: &str) -> Result<&str> {
+    let path = cgroup
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))
+        .context("not in a cgroup v2 hierarchy")?;
+    Ok(path.trim_end_matches('/'))
+}
+
+/// Iterate over `dir` and its ancestors up to the mount root. The mount root is
+/// the root cgroup on the host but may be limited in a container with a
+/// private cgroup namespace.
+fn ancestors(dir: &str) -> impl Iterator<Item = &str> {
+    std::iter::successors(Some(dir), |dir| Some(dir.rsplit_once('/')?.0))
+        .take_while(|dir| dir.len() >= SYS_FS_CGROUP.len())
+}
+
+/// Parse `cpu.stat`.
+fn parse_cpu_stat(stat: &str) -> Result<CpuStat> {
+    let (mut usage, mut user, mut system) = (None, None, None);
+    for line in stat.lines() {
+        let (key, value) = match line.split_once(' ') {
+            Some(x) => x,
+            None => continue,
+        };
+        let field = match key {
+            "usage_usec" => &mut usage,
+            "user_usec" => &mut user,
+            "system_usec" => &mut system,
+            _ => continue,
+        };
+        *field = Some(
+            value
+                .parse::<u64>()
+                .with_context(|| format!("failed to parse line '{line}'"))?,
+        );
+    }
+    Ok(CpuStat {
+        usage: usage.context("usage_usec is absent")?,
+        user: user.context("user_usec is absent")?,
+        system: system.context("system_usec is absent")?,
+    })
+}
+
+/// Parse `cpu.max` (`$MAX $PERIOD`), returning the quota measured in CPUs.
+/// Returns `None` if there is no limit.
+fn parse_cpu_max(cpu_max: &str) -> Result<Option<f64>> {
+    let (max, period) = cpu_max
+        .trim()
+        .split_once(' ')
+        .context("expected two fields")?;
+    if max == "max" {
+        return Ok(None);
+    }
+    let (max, period): (u64, u64) = (max.parse()?, period.parse()?);
+    anyhow::ensure!(period > 0, "zero period");
+    Ok(Some(max as f64 / period as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sysinfo::System as _;
+    use std::time::Duration;
+
+    const DIR: &str = "/sys/fs/cgroup/system.slice/docker-0123abcd.scope";
+
+    /// `cpu.stat` in `dir`, `SYS_CPU_ONLINE` with 8 CPUs, and `extra`
+    fn files(dir: &str, usage: u64, user: u64, system: u64, extra: &[(&str, &str)]) -> Files {
+        let stat = format!(
+            "usage_usec {usage}\nuser_usec {user}\nsystem_usec {system}\n\
+            nr_periods 0\nnr_throttled 0\nthrottled_usec 0\n"
+        );
+        let path = format!("{dir}/cpu.stat");
+        let mut entries = vec![(path.as_str(), stat.as_str()), (SYS_CPU_ONLINE, "0-7\n")];
+        entries.extend_from_slice(extra);
+        Files::from_entries(&entries)
+    }
+
+    #[test]
+    fn refresh() {
+        let files = |usage, user, system| {
+            files(
+                DIR,
+                usage,
+                user,
+                system,
+                &[
+                    (
+                        "/sys/fs/cgroup/system.slice/docker-0123abcd.scope/cpu.max",
+                        "250000 100000\n",
+                    ),
+                    ("/sys/fs/cgroup/system.slice/cpu.max", "max 100000\n"),
+                ],
+            )
+        };
+        let mut system = CgroupSystem::default();
+        let t0 = Instant::now();
+        system.refresh_cpus_from(DIR, &files(0, 0, 0), t0).unwrap();
+        assert_eq!(system.num_cpus(), 3);
+        assert_eq!(system.iter_cpu_usage().collect::<Vec<_>>(), [None; 3]);
+
+        system
+            .refresh_cpus_from(
+                DIR,
+                &files(2_500_000, 2_000_000, 500_000),
+                t0 + Duration::from_secs(2),
+            )
+            .unwrap();
+        assert_eq!(system.iter_cpu_usage().collect::<Vec<_>>(), [Some(0.5); 3]);
+        let times = system.iter_cpu_times().next().unwrap().unwrap();
+        assert_eq!(times[CpuTime::User], 0.4);
+        assert_eq!(times[CpuTime::System], 0.1);
+    }
+
+    #[test]
+    fn cpuset_and_quota() {
+        // 2.5 CPUs' worth of time, but only 2 CPUs to spend it on
+        let mut system = CgroupSystem::default();
+        let cpu_max = (
+            "/sys/fs/cgroup/system.slice/docker-0123abcd.scope/cpu.max",
+            "250000 100000\n",
+        );
+        let cpuset = (
+            "/sys/fs/cgroup/system.slice/docker-0123abcd.scope/cpuset.cpus.effective",
+            "2,5\n",
+        );
+        let t0 = Instant::now();
+        system
+            .refresh_cpus_from(DIR, &files(DIR, 0, 0, 0, &[cpu_max, cpuset]), t0)
+            .unwrap();
+        assert_eq!(system.num_cpus(), 2);
+        system
+            .refresh_cpus_from(
+                DIR,
+                &files(DIR, 2_000_000, 2_000_000, 0, &[cpu_max, cpuset]),
+                t0 + Duration::from_secs(1),
+            )
+            .unwrap();
+        assert_eq!(system.iter_cpu_usage().collect::<Vec<_>>(), [Some(1.0); 2]);
+
+        // Without a quota
+        system
+            .refresh_cpus_from(DIR, &files(DIR, 0, 0, 0, &[cpuset]), t0)
+            .unwrap();
+        assert_eq!(system.num_cpus(), 2);
+    }
+
+    #[test]
+    fn namespace_root() {
+        // `/proc/self/cgroup` reads `0::/` in a container with a private
+        // cgroup namespace, whose limit is at the mount root
+        let mut system = CgroupSystem::default();
+        let cpu_max = ("/sys/fs/cgroup/cpu.max", "150000 100000\n");
+        system
+            .refresh_cpus_from(
+                SYS_FS_CGROUP,
+                &files(SYS_FS_CGROUP, 0, 0, 0, &[cpu_max]),
+                Instant::now(),
+            )
+            .unwrap();
+        assert_eq!(system.num_cpus(), 2);
+
+        // The host's root cgroup has no `cpu.max`
+        system
+            .refresh_cpus_from(
+                SYS_FS_CGROUP,
+                &files(SYS_FS_CGROUP, 0, 0, 0, &[]),
+                Instant::now(),
+            )
+            .unwrap();
+        assert_eq!(system.num_cpus(), 8);
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            parse_cgroup_path("0::/user.slice/user-1000.slice/session-2.scope\n").unwrap(),
+            "/user.slice/user-1000.slice/session-2.scope"
+        );
+        assert_eq!(parse_cgroup_path("0::/\n").unwrap(), "");
+        assert!(parse_cgroup_path("12:cpu,cpuacct:/\n").is_err());
+
+        assert_eq!(
+            ancestors("/sys/fs/cgroup/a/b").collect::<Vec<_>>(),
+            ["/sys/fs/cgroup/a/b", "/sys/fs/cgroup/a", "/sys/fs/cgroup"]
+        );
+        assert_eq!(
+            ancestors("/sys/fs/cgroup").collect::<Vec<_>>(),
+            ["/sys/fs/cgroup"]
+        );
+
+        assert_eq!(parse_cpu_max("max 100000\n").unwrap(), None);
+        assert_eq!(parse_cpu_max("150000 100000\n").unwrap(), Some(1.5));
+        assert!(parse_cpu_max("150000\n").is_err());
+    }
+}
@@ -0,0 +1,99 @@
+//! CPU frequency scaling (`/sys/devices/system/cpu/cpuN/cpufreq`)
+use anyhow::Result;
+
+use crate::{process::Files, sysinfo::CpuFreq};
+
+pub const SYS_CPUFREQ: [&str; 3] = [
+    "/sys/devices/system/cpu/cpu[0-9]*/cpufreq/scaling_cur_freq",
+    "/sys/devices/system/cpu/cpu[0-9]*/cpufreq/scaling_min_freq",
+    "/sys/devices/system/cpu/cpu[0-9]*/cpufreq/scaling_max_freq",
+];
+
+/// Get the frequency of each CPU that has all of `scaling_{cur,min,max}_freq`,
+/// sorted by CPU ID.
+pub fn parse(files: &Files) -> Result<Vec<(usize, CpuFreq)>> {
+    let attrs = super::parse_cpu_attrs::<u64, 3>(
+        files,
+        "cpufreq",
+        ["scaling_cur_freq", "scaling_min_freq", "scaling_max_freq"],
+    )?;
+    Ok(attrs
+        .into_iter()
+        .filter_map(|(id, [cur, min, max])| {
+            Some((
+                id,
+                CpuFreq {
+                    cur: cur?,
+                    min: min?,
+                    max: max?,
+                },
+            ))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_cpufreq() {
+        let files = Files::from_entries(&[
+            (
+                "/sys/devices/system/cpu/cpu0/cpufreq/scaling_cur_freq",
+                "800000\n",
+            ),
+            (
+                "/sys/devices/system/cpu/cpu10/cpufreq/scaling_cur_freq",
+                "4700000\n",
+            ),
+            (
+                "/sys/devices/system/cpu/cpu2/cpufreq/scaling_cur_freq",
+                "2400000\n",
+            ),
+            (
+                "/sys/devices/system/cpu/cpu0/cpufreq/scaling_min_freq",
+                "800000\n",
+            ),
+            (
+                "/sys/devices/system/cpu/cpu10/cpufreq/scaling_min_freq",
+                "800000\n",
+            ),
+            (
+                "/sys/devices/system/cpu/cpu0/cpufreq/scaling_max_freq",
+                "5000000\n",
+            ),
+            (
+                "/sys/devices/system/cpu/cpu10/cpufreq/scaling_max_freq",
+                "5000000\n",
+            ),
+        ]);
+        assert_eq!(
+            parse(&files).unwrap(),
+            [
+                (
+                    0,
+                    CpuFreq {
+                        cur: 800000,
+                        min: 800000,
+                        max: 5000000,
+                    }
+                ),
+                (
+                    10,
+                    CpuFreq {
+                        cur: 4700000,
+                        min: 800000,
+                        max: 5000000,
+                    }
+                ),
+            ]
+        );
+
+        let files = Files::from_entries(&[(
+            "/sys/devices/system/cpu/cpu0/cpufreq/scaling_cur_freq",
+            "fast\n",
+        )]);
+        assert!(parse(&files).is_err());
+    }
+}
@@ -0,0 +1,88 @@
+//! The CPU list format (e.g., `0-3,8,10-11`) used throughout sysfs and procfs
+use anyhow::{Context, Result};
+
+/// A set of CPU IDs
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CpuSet(Vec<u64>);
+
+impl CpuSet {
+    /// Parse a CPU list. Surrounding whitespace is ignored.
+    pub fn parse(list: &str) -> Result<Self> {
+        let mut this = Self::default();
+        for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+            (|| {
+                let (start, end) = range.split_once('-').unwrap_or((range, range));
+                let (start, end): (usize, usize) = (start.parse()?, end.parse()?);
+                anyhow::ensure!(start <= end, "reversed range");
+                for id in start..=end {
+                    this.insert(id);
+                }
+                Ok(())
+            })()
+            .with_context(|| format!("invalid CPU range '{range}'"))?;
+        }
+        Ok(this)
+    }
+
+    #[inline]
+    pub fn contains(&self, id: usize) -> bool {
+        self.0
+            .get(id / 64)
+            .map_or(false, |word| word & (1 << (id % 64)) != 0)
+    }
+
+    pub fn insert(&mut self, id: usize) {
+        if self.0.len() <= id / 64 {
+            self.0.resize(id / 64 + 1, 0);
+        }
+        self.0[id / 64] |= 1 << (id % 64);
+    }
+
+    /// Get the number of CPUs in the set.
+    pub fn count(&self) -> usize {
+        self.0.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// Get the smallest CPU ID in the set.
+    pub fn min(&self) -> Option<usize> {
+        let (i, word) = self.0.iter().enumerate().find(|&(_, &word)| word != 0)?;
+        Some(i * 64 + word.trailing_zeros() as usize)
+    }
+
+    /// Get the largest CPU ID in the set.
+    pub fn max(&self) -> Option<usize> {
+        let (i, word) = self.0.iter().enumerate().rfind(|&(_, &word)| word != 0)?;
+        Some(i * 64 + 63 - word.leading_zeros() as usize)
+    }
+}
+
+impl std::str::FromStr for CpuSet {
+    type Err = anyhow::Error;
+
+    fn from_str(list: &str) -> Result<Self> {
+        Self::parse(list)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let set = CpuSet::parse("0-3,8,63-65\n").unwrap();
+        let ids: Vec<_> = (0..1000).filter(|&id| set.contains(id)).collect();
+        assert_eq!(ids, [0, 1, 2, 3, 8, 63, 64, 65]);
+        assert_eq!(set.max(), Some(65));
+        assert_eq!(set.min(), Some(0));
+        assert_eq!(CpuSet::parse("64,66").unwrap().min(), Some(64));
+        assert_eq!(set.count(), 8);
+
+        let set = CpuSet::parse("\n").unwrap();
+        assert_eq!(set.max(), None);
+
+        assert!(CpuSet::parse("0-").is_err());
+        assert!(CpuSet::parse("3-1").is_err());
+        assert!(CpuSet::parse("a").is_err());
+    }
+}
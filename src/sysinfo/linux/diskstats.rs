@@ -0,0 +1,176 @@
+//! Block device statistics from `/proc/diskstats`
+use anyhow::{Context, Result};
+use std::time::Instant;
+
+use crate::{
+    process::Files,
+    sysinfo::{DiskInfo, DiskRates},
+};
+
+pub const PROC_DISKSTATS: &str = "/proc/diskstats";
+/// Only whole disks have entries in `/sys/block`.
+pub const SYS_BLOCK_DEV: &str = "/sys/block/*/dev";
+
+/// The unit of `sectors_read` and `sectors_written`, regardless of the actual
+/// sector size
+const SECTOR_SIZE: f64 = 512.0;
+
+#[derive(Debug, Default)]
+pub struct DiskStats {
+    /// The time of the last sample
+    last_time: Option<Instant>,
+    disks: Vec<Disk>,
+}
+
+#[derive(Debug)]
+struct Disk {
+    name: String,
+    partition: bool,
+    counters: Counters,
+    /// `None` if unknown
+    rates: Option<DiskRates>,
+}
+
+/// `(sectors_read, sectors_written, io_ticks)`
+type Counters = [u64; 3];
+
+impl DiskStats {
+    pub fn refresh(&mut self, files: &Files, now: Instant) -> Result<()> {
+        let diskstats = files
+            .get(PROC_DISKSTATS)
+            .context("failed to read /proc/diskstats")?;
+        let elapsed = self
+            .last_time
+            .map(|last_time| now.saturating_duration_since(last_time).as_secs_f64())
+            .filter(|&elapsed| elapsed > 0.0);
+
+        // `/sys/block/sda/dev` → `sda`. `/` in device names is replaced with
+        // `!` in sysfs.
+        let whole_disks: Vec<String> = files
+            .iter()
+            .filter(|(_, content)| content.is_some())
+            .filter_map(|(path, _)| {
+                let name = path.strip_prefix("/sys/block/")?.strip_suffix("/dev")?;
+                Some(name.replace('!', "/"))
+            })
+            .collect();
+
+        let disks = diskstats
+            .lines()
+            .map(|line| {
+                let (name, counters) =
+                    parse_line(line).with_context(|| format!("failed to parse line '{line}'"))?;
+                let last = self.disks.iter().find(|d| d.name == name);
+                let rates = last.zip(elapsed).and_then(|(last, elapsed)| {
+                    let mut deltas = [0; 3];
+                    for ((delta, new), old) in deltas.iter_mut().zip(counters).zip(last.counters) {
+                        // Fails if the device was re-created
+                        *delta = new.checked_sub(old)?;
+                    }
+                    Some(DiskRates {
+                        read_bytes: deltas[0] as f64 * SECTOR_SIZE / elapsed,
+                        write_bytes: deltas[1] as f64 * SECTOR_SIZE / elapsed,
+                        utilization: (deltas[2] as f64 * 1.0e-3 / elapsed).min(1.0),
+                    })
+                });
+                Ok(Disk {
+                    name: name.to_owned(),
+                    partition: !whole_disks.iter().any(|d| d == name),
+                    counters,
+                    rates,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        self.disks = disks;
+        self.last_time = Some(now);
+        Ok(())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = DiskInfo<'_>> {
+        self.disks.iter().map(|d| DiskInfo {
+            name: &d.name,
+            partition: d.partition,
+            rates: d.rates,
+        })
+    }
+}
+
+/// Parse a line like
+/// `8 0 sda 4527 1253 353914 1577 2841 3087 163562 5240 0 4504 7128 0 0 0 0`.
+fn parse_line(line: &str) -> Result<(&str, Counters)> {
+    let mut fields = line.split_ascii_whitespace().skip(2);
+    let name = fields.next().context("device name is absent")?;
+    let fields = fields
+        .map(str::parse::<u64>)
+        .collect::<Result<Vec<_>, _>>()?;
+    // `reads_completed reads_merged sectors_read time_reading
+    // writes_completed writes_merged sectors_written time_writing
+    // ios_in_progress io_ticks ...`
+    anyhow::ensure!(fields.len() >= 10, "too few columns");
+    Ok((name, [fields[2], fields[6], fields[9]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn files(diskstats: &str) -> Files {
+        Files::from_entries(&[
+            (PROC_DISKSTATS, diskstats),
+            ("/sys/block/loop0/dev", "7:0\n"),
+            ("/sys/block/nvme0n1/dev", "259:0\n"),
+        ])
+    }
+
+    #[test]
+    fn refresh() {
+        let mut stats = DiskStats::default();
+        let t0 = Instant::now();
+        stats
+            .refresh(
+                &files(
+                    "   7       0 loop0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0
+ 259       0 nvme0n1 4527 1253 353914 1577 2841 3087 163562 5240 0 4504 7128 0 0 0 0 0 0
+ 259       1 nvme0n1p1 200 0 4000 60 3 0 8 1 0 70 61 0 0 0 0 0 0
+",
+                ),
+                t0,
+            )
+            .unwrap();
+        let disks: Vec<_> = stats
+            .iter()
+            .map(|d| (d.name, d.partition, d.rates))
+            .collect();
+        assert_eq!(
+            disks,
+            [
+                ("loop0", false, None),
+                ("nvme0n1", false, None),
+                ("nvme0n1p1", true, None)
+            ]
+        );
+
+        stats
+            .refresh(
+                &files(
+                    "   7       0 loop0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0
+ 259       0 nvme0n1 4627 1253 357914 1677 2941 3087 165562 5340 0 5004 7328 0 0 0 0 0 0
+ 259       1 nvme0n1p1 200 0 4000 60 3 0 8 1 0 70 61 0 0 0 0 0 0
+",
+                ),
+                t0 + Duration::from_secs(2),
+            )
+            .unwrap();
+        let nvme = stats.iter().nth(1).unwrap().rates.unwrap();
+        assert_eq!(
+            nvme,
+            DiskRates {
+                read_bytes: 4000.0 * 512.0 / 2.0,
+                write_bytes: 2000.0 * 512.0 / 2.0,
+                utilization: 0.25,
+            }
+        );
+    }
+}
@@ -0,0 +1,196 @@
+//! Per-CPU interrupt counts from `/proc/interrupts` and `/proc/softirqs`
+use anyhow::{Context, Result};
+use std::time::Instant;
+
+use crate::{process::Files, sysinfo::InterruptRates};
+
+pub const PROC_INTERRUPTS: &str = "/proc/interrupts";
+pub const PROC_SOFTIRQS: &str = "/proc/softirqs";
+
+#[derive(Debug, Default)]
+pub struct Interrupts {
+    /// The time of the last sample
+    last_time: Option<Instant>,
+    /// Indexed by CPU ID. `None` for CPUs absent from the last sample (e.g.,
+    /// because they are offline).
+    counts: Vec<Option<Counters>>,
+    /// Indexed by CPU ID. `None` if unknown.
+    rates: Vec<Option<InterruptRates>>,
+}
+
+/// `(hardware interrupts, softirqs)`
+type Counters = [u64; 2];
+
+impl Interrupts {
+    pub fn refresh(&mut self, files: &Files, now: Instant) -> Result<()> {
+        let irqs = files
+            .get(PROC_INTERRUPTS)
+            .context("failed to read /proc/interrupts")
+            .and_then(parse)
+            .context("failed to parse /proc/interrupts")?;
+        let softirqs = files
+            .get(PROC_SOFTIRQS)
+            .context("failed to read /proc/softirqs")
+            .and_then(parse)
+            .context("failed to parse /proc/softirqs")?;
+        let elapsed = self
+            .last_time
+            .map(|last_time| now.saturating_duration_since(last_time).as_secs_f64())
+            .filter(|&elapsed| elapsed > 0.0);
+
+        let num_cpus = irqs.len().max(softirqs.len());
+        let counts: Vec<Option<Counters>> = (0..num_cpus)
+            .map(|id| Some([(*irqs.get(id)?)?, (*softirqs.get(id)?)?]))
+            .collect();
+        self.rates = counts
+            .iter()
+            .enumerate()
+            .map(|(id, counters)| {
+                let (counters, last) = (*counters).zip(*self.counts.get(id)?)?;
+                let elapsed = elapsed?;
+                // Fails if the counters were reset
+                let rate = |i: usize| Some(counters[i].checked_sub(last[i])? as f64 / elapsed);
+                Some(InterruptRates {
+                    irqs: rate(0)?,
+                    softirqs: rate(1)?,
+                })
+            })
+            .collect();
+        self.counts = counts;
+        self.last_time = Some(now);
+        Ok(())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Option<InterruptRates>> + '_ {
+        self.rates.iter().copied()
+    }
+}
+
+/// The rows of `/proc/interrupts` that aren't broken down by CPU: erroneous
+/// and missed interrupts on x86, and erroneous ones on Arm
+const SYSTEM_WIDE_ROWS: [&str; 3] = ["ERR", "MIS", "Err"];
+
+/// Parse `/proc/interrupts` or `/proc/softirqs` into the total count of each
+/// CPU, indexed by CPU ID. The CPUs missing from the header (e.g., offline
+/// ones) get `None`.
+fn parse(table: &str) -> Result<Vec<Option<u64>>> {
+    let mut lines = table.lines();
+    let ids = lines
+        .next()
+        .context("header is absent")?
+        .split_ascii_whitespace()
+        .map(|column| {
+            column
+                .strip_prefix("CPU")
+                .and_then(|id| id.parse::<usize>().ok())
+                .with_context(|| format!("invalid column '{column}'"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut totals = vec![None; ids.iter().max().map_or(0, |&id| id + 1)];
+    for &id in ids.iter() {
+        totals[id] = Some(0u64);
+    }
+    for line in lines {
+        let (label, counts) = match line.split_once(':') {
+            Some(x) => x,
+            None => continue,
+        };
+        // Skip the system-wide rows, which have a single count
+        if SYSTEM_WIDE_ROWS.contains(&label.trim()) {
+            continue;
+        }
+        let counts: Vec<u64> = counts
+            .split_ascii_whitespace()
+            .take(ids.len())
+            .map_while(|count| count.parse().ok())
+            .collect();
+        // Can't tell which CPUs the counts of a short row belong to
+        if counts.len() < ids.len() {
+            continue;
+        }
+        for (&id, count) in ids.iter().zip(counts) {
+            totals[id] = totals[id].map(|total| total + count);
+        }
+    }
+    Ok(totals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn refresh() {
+        let files = |timer, nic, net_rx| {
+            let interrupts = format!(
+                "           CPU0       CPU2       \n  \
+                0:         44          0   IO-APIC   2-edge      timer\n \
+                24:          0 {nic:>10}   PCI-MSI 524288-edge      eth0\n\
+                LOC: {timer:>10} {timer:>10}   Local timer interrupts\n\
+                ERR:          0\n\
+                MIS:          0\n"
+            );
+            let softirqs = format!(
+                "                    CPU0       CPU2       \n          \
+                HI:          1          0\n       \
+                TIMER: {timer:>10} {timer:>10}\n      \
+                NET_RX:          0 {net_rx:>10}\n"
+            );
+            (interrupts, softirqs)
+        };
+        let refresh = |interrupts: &mut Interrupts, (proc_interrupts, proc_softirqs), now| {
+            let files = Files::from_entries(&[
+                (PROC_INTERRUPTS, proc_interrupts),
+                (PROC_SOFTIRQS, proc_softirqs),
+            ]);
+            interrupts.refresh(&files, now).unwrap();
+        };
+
+        let mut interrupts = Interrupts::default();
+        let t0 = Instant::now();
+        let (i0, s0) = files(1000, 5000, 20000);
+        refresh(&mut interrupts, (i0.as_str(), s0.as_str()), t0);
+        assert_eq!(interrupts.iter().collect::<Vec<_>>(), [None; 3]);
+
+        let (i1, s1) = files(1500, 105000, 60000);
+        refresh(
+            &mut interrupts,
+            (i1.as_str(), s1.as_str()),
+            t0 + Duration::from_secs(2),
+        );
+        assert_eq!(
+            interrupts.iter().collect::<Vec<_>>(),
+            [
+                Some(InterruptRates {
+                    irqs: 250.0,
+                    softirqs: 250.0,
+                }),
+                // CPU1 is offline
+                None,
+                Some(InterruptRates {
+                    irqs: 50250.0,
+                    softirqs: 20250.0,
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_table() {
+        assert_eq!(
+            parse("   CPU0   CPU1\n  0:  5  6  IO-APIC timer\n  1:  1  2\n").unwrap(),
+            [Some(6), Some(8)]
+        );
+        assert_eq!(parse("   CPU1\n  0:  5\n").unwrap(), [None, Some(5)]);
+        // The system-wide rows look like per-CPU ones with one CPU
+        assert_eq!(
+            parse("   CPU0\n  0:  5  IO-APIC timer\nLOC:  7  Local timer\nERR:  3\nMIS:  4\n")
+                .unwrap(),
+            [Some(12)]
+        );
+        assert!(parse("").is_err());
+        assert!(parse("   CPU0   GPU1\n").is_err());
+    }
+}
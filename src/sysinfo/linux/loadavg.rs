@@ -0,0 +1,76 @@
+//! `/proc/loadavg` and the run queue counters in `/proc/stat`
+use anyhow::{Context, Result};
+
+use crate::sysinfo::LoadInfo;
+
+pub const PROC_LOADAVG: &str = "/proc/loadavg";
+
+/// Parse `/proc/loadavg` (e.g., `0.52 0.58 0.59 2/1234 56789`) and the
+/// `procs_running` and `procs_blocked` lines in `/proc/stat`.
+pub fn parse(loadavg: &str, stat: &str) -> Result<LoadInfo> {
+    let mut info = LoadInfo::default();
+
+    (|| {
+        let mut fields = loadavg.split_ascii_whitespace();
+        for avg in info.avg.iter_mut() {
+            *avg = fields.next().context("too few fields")?.parse()?;
+        }
+        let (runnable, total) = fields
+            .next()
+            .and_then(|tasks| tasks.split_once('/'))
+            .context("task counts are absent")?;
+        info.runnable_tasks = runnable.parse()?;
+        info.total_tasks = total.parse()?;
+        Ok(()) as Result<()>
+    })()
+    .with_context(|| format!("failed to parse '{}'", loadavg.trim_end()))?;
+
+    let (mut procs_running, mut procs_blocked) = (None, None);
+    for line in stat.lines() {
+        let (key, value) = match line.split_once(' ') {
+            Some(x) => x,
+            None => continue,
+        };
+        let field = match key {
+            "procs_running" => &mut procs_running,
+            "procs_blocked" => &mut procs_blocked,
+            _ => continue,
+        };
+        *field = Some(
+            value
+                .parse()
+                .with_context(|| format!("failed to parse line '{line}'"))?,
+        );
+    }
+    info.procs_running = procs_running.context("procs_running is absent")?;
+    info.procs_blocked = procs_blocked.context("procs_blocked is absent")?;
+
+    Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_loadavg() {
+        let info = parse(
+            "12.52 8.58 4.59 9/1234 56789\n",
+            "cpu0 1 2 3 4\nprocs_running 10\nprocs_blocked 2\n",
+        )
+        .unwrap();
+        assert_eq!(
+            info,
+            LoadInfo {
+                avg: [12.52, 8.58, 4.59],
+                runnable_tasks: 9,
+                total_tasks: 1234,
+                procs_running: 10,
+                procs_blocked: 2,
+            }
+        );
+
+        assert!(parse("0.1 0.2\n", "procs_running 1\nprocs_blocked 0\n").is_err());
+        assert!(parse("0.1 0.2 0.3 1/2 3\n", "procs_running 1\n").is_err());
+    }
+}
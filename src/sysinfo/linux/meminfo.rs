@@ -0,0 +1,86 @@
+//! `/proc/meminfo`
+use anyhow::{Context, Result};
+
+use crate::sysinfo::MemoryInfo;
+
+pub fn parse(meminfo: &str) -> Result<MemoryInfo> {
+    let mut info = MemoryInfo::default();
+    let mut free = None;
+    let mut available = None;
+    for line in meminfo.lines() {
+        let (key, value) = match line.split_once(':') {
+            Some(x) => x,
+            None => continue,
+        };
+        let field = match key {
+            "MemTotal" => &mut info.total,
+            "MemFree" => free.get_or_insert(0),
+            "MemAvailable" => available.get_or_insert(0),
+            "Buffers" => &mut info.buffers,
+            "Cached" => &mut info.cached,
+            "Shmem" => &mut info.shmem,
+            "Dirty" => &mut info.dirty,
+            _ => continue,
+        };
+        *field = parse_kib(value).with_context(|| format!("failed to parse line '{line}'"))?;
+    }
+
+    anyhow::ensure!(info.total != 0, "MemTotal is absent");
+
+    // `MemAvailable` appeared in Linux 3.14. Approximate it on older kernels.
+    info.available = available
+        .or_else(|| Some(free? + info.buffers + info.cached))
+        .context("neither MemAvailable nor MemFree is present")?;
+
+    Ok(info)
+}
+
+/// Parse a value like `16318412 kB`, returning the number of bytes.
+pub fn parse_kib(value: &str) -> Result<u64> {
+    let value = value.trim();
+    let value = value.strip_suffix(" kB").unwrap_or(value);
+    let kib: u64 = value.parse()?;
+    Ok(kib * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_meminfo() {
+        let info = parse(
+            "MemTotal:       16318412 kB
+MemFree:         1178252 kB
+MemAvailable:    9873400 kB
+Buffers:          713964 kB
+Cached:          7790284 kB
+SwapCached:        18676 kB
+Active:          6822020 kB
+Shmem:            520748 kB
+Dirty:              1364 kB
+HugePages_Total:       0
+",
+        )
+        .unwrap();
+        assert_eq!(
+            info,
+            MemoryInfo {
+                total: 16318412 * 1024,
+                available: 9873400 * 1024,
+                buffers: 713964 * 1024,
+                cached: 7790284 * 1024,
+                shmem: 520748 * 1024,
+                dirty: 1364 * 1024,
+            }
+        );
+
+        // Linux < 3.14
+        let info =
+            parse("MemTotal: 100 kB\nMemFree: 10 kB\nBuffers: 5 kB\nCached: 20 kB\n").unwrap();
+        assert_eq!(info.available, 35 * 1024);
+
+        assert!(parse("").is_err());
+        assert!(parse("MemTotal: lots\n").is_err());
+    }
+}
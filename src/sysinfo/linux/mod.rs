@@ -0,0 +1,830 @@
+use anyhow::{Context, Result};
+
+use self::cpulist::CpuSet;
+use super::{
+    ActivityRates, BatteryInfo, CpuFreq, CpuInfo, CpuTime, CpuTimes, CpuTopology, DiskInfo,
+    InterruptRates, LoadInfo, MemoryInfo, NetInterface, PowerInfo, PressureInfo, ProcessInfo,
+    Sensor, SwapInfo,
+};
+use crate::{iter::BoxMiniIterator, process::Files, slist};
+
+mod activity;
+mod cgroup;
+mod cpufreq;
+mod cpulist;
+mod diskstats;
+mod interrupts;
+mod loadavg;
+mod meminfo;
+mod netdev;
+mod power_supply;
+mod processes;
+mod psi;
+mod rapl;
+mod swap;
+mod thermal;
+mod topology;
+
+pub use self::cgroup::CgroupSystem;
+
+const PROC_STAT: &str = "/proc/stat";
+const SYS_CPU_ONLINE: &str = "/sys/devices/system/cpu/online";
+const SYS_CPU_PRESENT: &str = "/sys/devices/system/cpu/present";
+const SYS_CPU_ISOLATED: &str = "/sys/devices/system/cpu/isolated";
+/// Absent unless the kernel is built with `CONFIG_NO_HZ_FULL`
+const SYS_CPU_NOHZ_FULL: &str = "/sys/devices/system/cpu/nohz_full";
+/// Commands run by [`crate::process`] inherit Zellij's CPU affinity.
+const PROC_SELF_STATUS: &str = "/proc/self/status";
+/// The files read by [`super::System::refresh_cpus`]
+const CPU_FILES: [&str; 7] = [
+    PROC_STAT,
+    SYS_CPU_ONLINE,
+    SYS_CPU_PRESENT,
+    SYS_CPU_ISOLATED,
+    SYS_CPU_NOHZ_FULL,
+    PROC_SELF_STATUS,
+    loadavg::PROC_LOADAVG,
+];
+const SYS_CPU_PREFIX: &str = "/sys/devices/system/cpu/cpu";
+
+#[derive(Debug, Default)]
+pub struct System {
+    /// Indexed by CPU ID. Includes offline CPUs.
+    cpus: slist::Link<Cpu>,
+    load: Option<LoadInfo>,
+    activity: activity::Activity,
+    memory: Option<MemoryInfo>,
+    swap: swap::Swap,
+    pressure: Option<PressureInfo>,
+    net_dev: netdev::NetDev,
+    disk_stats: diskstats::DiskStats,
+    thermal: thermal::Thermal,
+    rapl: rapl::Rapl,
+    interrupts: interrupts::Interrupts,
+    batteries: Vec<power_supply::Battery>,
+    processes: processes::Processes,
+}
+
+#[derive(Debug, Default)]
+struct Cpu {
+    online: bool,
+    allowed: bool,
+    isolated: bool,
+    nohz_full: bool,
+    /// The latest sample. `None` if there hasn't been one yet or the CPU is
+    /// offline.
+    stats: Option<CpuStats>,
+    /// The difference between the last two samples. `None` if it's unknown.
+    delta: Option<CpuStats>,
+    freq: Option<CpuFreq>,
+    topology: Option<CpuTopology>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct CpuStats {
+    total: u64,
+    active: u64,
+    /// Indexed by [`CpuTime`]
+    times: [u64; CpuTime::COUNT],
+}
+
+/// The columns of a `cpuN` line in `/proc/stat`. Older kernels omit trailing
+/// columns (`iowait`, `irq`, and `softirq` appeared in Linux 2.5.41, `steal`
+/// in 2.6.11, `guest` in 2.6.24, and `guest_nice` in 2.6.33).
+mod column {
+    pub const USER: usize = 0;
+    pub const NICE: usize = 1;
+    pub const SYSTEM: usize = 2;
+    pub const IDLE: usize = 3;
+    pub const IOWAIT: usize = 4;
+    pub const IRQ: usize = 5;
+    pub const SOFTIRQ: usize = 6;
+    pub const STEAL: usize = 7;
+    pub const GUEST: usize = 8;
+    pub const COUNT: usize = 10;
+}
+
+/// The `/proc/stat` column index of each [`CpuTime`]
+const CPU_TIME_COLUMNS: [usize; CpuTime::COUNT] = {
+    let mut columns = [0; CpuTime::COUNT];
+    columns[CpuTime::User as usize] = column::USER;
+    columns[CpuTime::Nice as usize] = column::NICE;
+    columns[CpuTime::System as usize] = column::SYSTEM;
+    columns[CpuTime::Iowait as usize] = column::IOWAIT;
+    columns[CpuTime::Irq as usize] = column::IRQ;
+    columns[CpuTime::Softirq as usize] = column::SOFTIRQ;
+    columns[CpuTime::Steal as usize] = column::STEAL;
+    columns
+};
+
+impl CpuStats {
+    /// Parse the numeric part of a `cpuN` line in `/proc/stat`.
+    fn parse(fields: &str) -> Result<Self> {
+        let mut columns = [0u64; column::COUNT];
+        let mut num_columns = 0;
+        // Columns added by future kernels are ignored because we can't tell
+        // whether they overlap with the known ones.
+        for (column, field) in columns.iter_mut().zip(fields.split_ascii_whitespace()) {
+            *column = field
+                .parse()
+                .with_context(|| format!("invalid number '{field}'"))?;
+            num_columns += 1;
+        }
+        anyhow::ensure!(
+            num_columns > column::IDLE,
+            "expected at least {} columns, found {num_columns}",
+            column::IDLE + 1
+        );
+
+        // `guest` and `guest_nice` are already accounted for in `user` and
+        // `nice`, respectively
+        let total: u64 = columns[..column::GUEST].iter().sum();
+        let idle = columns[column::IDLE] + columns[column::IOWAIT];
+
+        Ok(Self {
+            total,
+            active: total - idle,
+            times: CPU_TIME_COLUMNS.map(|i| columns[i]),
+        })
+    }
+}
+
+impl CpuStats {
+    /// Calculate the difference from an earlier sample `earlier`. Returns
+    /// `None` if the counters have been reset (e.g., by CPU hotplug) or no time
+    /// has elapsed, in which case the difference doesn't tell anything.
+    fn delta_since(&self, earlier: &Self) -> Option<Self> {
+        let total = self.total.checked_sub(earlier.total)?;
+        let active = self.active.checked_sub(earlier.active)?;
+        if total == 0 {
+            return None;
+        }
+
+        let mut times = self.times;
+        for (x, y) in times.iter_mut().zip(earlier.times) {
+            // `iowait` is known to go backwards occasionally, so don't treat
+            // it as a reset
+            *x = x.saturating_sub(y);
+        }
+
+        Some(Self {
+            total,
+            active,
+            times,
+        })
+    }
+}
+
+/// Collect the attributes `names` from `/sys/devices/system/cpu/cpuN/` `dir`
+/// (which may be empty) in `files`. Returns the CPUs that have at least one of
+/// them, sorted by CPU ID.
+fn parse_cpu_attrs<T, const N: usize>(
+    files: &Files,
+    dir: &str,
+    names: [&str; N],
+) -> Result<Vec<(usize, [Option<T>; N])>>
+where
+    T: std::str::FromStr,
+    T::Err: Into<anyhow::Error>,
+{
+    let mut cpus: Vec<(usize, [Option<T>; N])> = Vec::new();
+    for (path, content) in files.iter() {
+        // `None` if the glob pattern didn't match anything or the CPU went
+        // offline in the meantime
+        let content = match content {
+            Some(x) => x,
+            None => continue,
+        };
+        let (id, name) = match path
+            .strip_prefix(SYS_CPU_PREFIX)
+            .and_then(|rest| rest.split_once('/'))
+            .and_then(|(id, rest)| {
+                if dir.is_empty() {
+                    Some((id, rest))
+                } else {
+                    Some((id, rest.strip_prefix(dir)?.strip_prefix('/')?))
+                }
+            }) {
+            Some(x) => x,
+            None => continue,
+        };
+        let index = match names.iter().position(|&x| x == name) {
+            Some(x) => x,
+            None => continue,
+        };
+        let id: usize = id
+            .parse()
+            .with_context(|| format!("invalid CPU ID in '{path}'"))?;
+        let value: T = content
+            .trim()
+            .parse()
+            .map_err(Into::into)
+            .with_context(|| format!("failed to parse '{path}'"))?;
+
+        let i = match cpus.binary_search_by_key(&id, |&(id, _)| id) {
+            Ok(i) => i,
+            Err(i) => {
+                cpus.insert(i, (id, [(); N].map(|()| None)));
+                i
+            }
+        };
+        cpus[i].1[index] = Some(value);
+    }
+    Ok(cpus)
+}
+
+/// Group the readable files in `files` by directory, yielding
+/// `(directory, [(file name, trimmed content)])` in the order of first
+/// appearance.
+fn group_by_dir(files: &Files) -> Vec<(&str, Vec<(&str, &str)>)> {
+    let mut dirs: Vec<(&str, Vec<(&str, &str)>)> = Vec::new();
+    for (path, content) in files.iter() {
+        // `None` if the glob pattern didn't match anything or the attribute
+        // failed to respond
+        let content = match content {
+            Some(x) => x.trim(),
+            None => continue,
+        };
+        let (dir, name) = match path.rsplit_once('/') {
+            Some(x) => x,
+            None => continue,
+        };
+        match dirs.iter_mut().find(|(d, _)| *d == dir) {
+            Some((_, attrs)) => attrs.push((name, content)),
+            None => dirs.push((dir, vec![(name, content)])),
+        }
+    }
+    dirs
+}
+
+/// Find an attribute in an output of [`group_by_dir`].
+fn get_attr<'a>(attrs: &[(&str, &'a str)], name: &str) -> Option<&'a str> {
+    attrs
+        .iter()
+        .find(|&&(n, _)| n == name)
+        .map(|&(_, value)| value)
+}
+
+impl System {
+    fn refresh_cpus_from(&mut self, files: &Files, now: std::time::Instant) -> Result<()> {
+        let stat = files.get(PROC_STAT).context("failed to read /proc/stat")?;
+        let mut new_stats = stat
+            .lines()
+            .filter_map(|line| {
+                let (name, fields) = line.split_once(' ')?;
+                let id = name.strip_prefix("cpu")?.parse::<usize>().ok()?;
+                Some(
+                    CpuStats::parse(fields)
+                        .map(|stats| (id, stats))
+                        .with_context(|| format!("failed to parse line '{line}'")),
+                )
+            })
+            .collect::<Result<Vec<_>>>()?;
+        anyhow::ensure!(!new_stats.is_empty(), "no CPUs found");
+        new_stats.sort_unstable_by_key(|&(id, _)| id);
+
+        // `/proc/stat` leaves out offline CPUs, so get the complete picture
+        // from sysfs if possible
+        let parse_cpu_list = |path| {
+            files
+                .get(path)
+                // Older kernels print this for an unset `nohz_full`
+                .filter(|list| list.trim() != "(null)")
+                .map(CpuSet::parse)
+                .transpose()
+                .with_context(|| format!("failed to parse '{path}'"))
+        };
+        let online = parse_cpu_list(SYS_CPU_ONLINE)?;
+        let present = parse_cpu_list(SYS_CPU_PRESENT)?;
+        let isolated = parse_cpu_list(SYS_CPU_ISOLATED)?.unwrap_or_default();
+        let nohz_full = parse_cpu_list(SYS_CPU_NOHZ_FULL)?.unwrap_or_default();
+        // Reflects both `sched_setaffinity` and `cpuset.cpus.effective`
+        let allowed = files
+            .get(PROC_SELF_STATUS)
+            .and_then(|status| {
+                status
+                    .lines()
+                    .find_map(|line| line.strip_prefix("Cpus_allowed_list:"))
+            })
+            .map(CpuSet::parse)
+            .transpose()
+            .context("failed to parse 'Cpus_allowed_list' in '/proc/self/status'")?;
+
+        let num_cpus = [
+            new_stats.last().map(|&(id, _)| id),
+            online.as_ref().and_then(CpuSet::max),
+            present.as_ref().and_then(CpuSet::max),
+        ]
+        .into_iter()
+        .flatten()
+        .max()
+        .map_or(0, |id| id + 1)
+        // Don't let CPUs disappear
+        .max(slist::iter(&self.cpus).count());
+
+        slist::resize_with(&mut self.cpus, num_cpus, |_| Cpu::default());
+        for (id, cpu) in slist::iter_mut(&mut self.cpus).enumerate() {
+            let new_stats = new_stats
+                .binary_search_by_key(&id, |&(id, _)| id)
+                .ok()
+                .map(|i| new_stats[i].1);
+            cpu.online = online
+                .as_ref()
+                .map_or(new_stats.is_some(), |online| online.contains(id));
+            cpu.allowed = allowed
+                .as_ref()
+                .map_or(true, |allowed| allowed.contains(id));
+            cpu.isolated = isolated.contains(id);
+            cpu.nohz_full = nohz_full.contains(id);
+            cpu.delta = cpu
+                .stats
+                .zip(new_stats)
+                .and_then(|(stats, new_stats)| new_stats.delta_since(&stats));
+            cpu.stats = new_stats;
+        }
+
+        self.load = files
+            .get(loadavg::PROC_LOADAVG)
+            .map(|loadavg| loadavg::parse(loadavg, stat))
+            .transpose()?;
+        self.activity
+            .refresh(stat, crate::process::num_forks(), now)?;
+
+        Ok(())
+    }
+}
+
+impl super::System for System {
+    fn refresh_cpus(&mut self) -> Result<()> {
+        let files = crate::process::read_files(&CPU_FILES);
+        self.refresh_cpus_from(&files, std::time::Instant::now())
+    }
+
+    fn num_cpus(&self) -> usize {
+        slist::iter(&self.cpus).count()
+    }
+
+    fn iter_cpu_usage(&self) -> BoxMiniIterator<'_, Option<f64>> {
+        Box::new(slist::iter(&self.cpus).map(|cpu| {
+            let stats = cpu.delta?;
+            Some(stats.active as f64 / stats.total as f64)
+        }))
+    }
+
+    fn iter_cpu_times(&self) -> BoxMiniIterator<'_, Option<CpuTimes>> {
+        Box::new(slist::iter(&self.cpus).map(|cpu| {
+            let stats = cpu.delta?;
+            Some(CpuTimes(stats.times.map(|x| x as f64 / stats.total as f64)))
+        }))
+    }
+
+    fn iter_cpu_info(&self) -> BoxMiniIterator<'_, CpuInfo> {
+        Box::new(
+            slist::iter(&self.cpus)
+                .enumerate()
+                .map(|(id, cpu)| CpuInfo {
+                    id,
+                    online: cpu.online,
+                    allowed: cpu.allowed,
+                    isolated: cpu.isolated,
+                    nohz_full: cpu.nohz_full,
+                }),
+        )
+    }
+
+    fn refresh_topology(&mut self) -> Result<()> {
+        let mut patterns = topology::SYS_CPU_TOPOLOGY.to_vec();
+        patterns.extend(topology::SYS_CPU_TOPOLOGY_EXTRA);
+        let topology = topology::parse(&crate::process::read_files(&patterns))?;
+        for (id, cpu) in slist::iter_mut(&mut self.cpus).enumerate() {
+            cpu.topology = topology
+                .binary_search_by_key(&id, |&(id, _)| id)
+                .ok()
+                .map(|i| topology[i].1);
+        }
+        Ok(())
+    }
+
+    fn iter_cpu_topology(&self) -> BoxMiniIterator<'_, Option<CpuTopology>> {
+        Box::new(slist::iter(&self.cpus).map(|cpu| cpu.topology))
+    }
+
+    fn refresh_cpu_freq(&mut self) -> Result<()> {
+        let files = crate::process::read_files(&cpufreq::SYS_CPUFREQ);
+        let freqs = cpufreq::parse(&files)?;
+        for (id, cpu) in slist::iter_mut(&mut self.cpus).enumerate() {
+            cpu.freq = freqs
+                .binary_search_by_key(&id, |&(id, _)| id)
+                .ok()
+                .map(|i| freqs[i].1);
+        }
+        Ok(())
+    }
+
+    fn iter_cpu_freq(&self) -> BoxMiniIterator<'_, Option<CpuFreq>> {
+        Box::new(slist::iter(&self.cpus).map(|cpu| cpu.freq))
+    }
+
+    fn refresh_interrupts(&mut self) -> Result<()> {
+        let files =
+            crate::process::read_files(&[interrupts::PROC_INTERRUPTS, interrupts::PROC_SOFTIRQS]);
+        self.interrupts.refresh(&files, std::time::Instant::now())
+    }
+
+    fn iter_cpu_interrupts(&self) -> BoxMiniIterator<'_, Option<InterruptRates>> {
+        Box::new(self.interrupts.iter())
+    }
+
+    fn load(&self) -> Option<LoadInfo> {
+        self.load
+    }
+
+    fn activity(&self) -> Option<ActivityRates> {
+        self.activity.rates
+    }
+
+    fn refresh_memory(&mut self) -> Result<()> {
+        let meminfo = crate::process::spawn_and_get_output(b"cat /proc/meminfo");
+        self.memory = Some(meminfo::parse(std::str::from_utf8(&meminfo)?)?);
+        Ok(())
+    }
+
+    fn memory(&self) -> Option<MemoryInfo> {
+        self.memory
+    }
+
+    fn refresh_swap(&mut self) -> Result<()> {
+        let files = crate::process::read_files(&[
+            swap::PROC_MEMINFO,
+            swap::PROC_VMSTAT,
+            swap::SYS_ZRAM_MM_STAT,
+        ]);
+        self.swap.refresh(&files, std::time::Instant::now())
+    }
+
+    fn swap(&self) -> Option<SwapInfo> {
+        self.swap.info
+    }
+
+    fn refresh_pressure(&mut self) -> Result<()> {
+        let files = crate::process::read_files(&[
+            psi::PROC_PRESSURE_CPU,
+            psi::PROC_PRESSURE_MEMORY,
+            psi::PROC_PRESSURE_IO,
+        ]);
+        self.pressure = psi::parse(&files)?;
+        Ok(())
+    }
+
+    fn pressure(&self) -> Option<PressureInfo> {
+        self.pressure
+    }
+
+    fn refresh_power(&mut self) -> Result<()> {
+        let files = crate::process::read_files(&rapl::SYS_RAPL);
+        self.rapl.refresh(&files, std::time::Instant::now())
+    }
+
+    fn power(&self) -> Option<PowerInfo> {
+        self.rapl.info
+    }
+
+    fn refresh_batteries(&mut self) -> Result<()> {
+        let files = crate::process::read_files(&power_supply::SYS_POWER_SUPPLY);
+        self.batteries = power_supply::parse(&files);
+        Ok(())
+    }
+
+    fn iter_batteries(&self) -> BoxMiniIterator<'_, BatteryInfo<'_>> {
+        Box::new(self.batteries.iter().map(power_supply::Battery::info))
+    }
+
+    fn refresh_network(&mut self) -> Result<()> {
+        let net_dev = crate::process::spawn_and_get_output(b"cat /proc/net/dev");
+        self.net_dev
+            .refresh(std::str::from_utf8(&net_dev)?, std::time::Instant::now())
+    }
+
+    fn iter_network(&self) -> BoxMiniIterator<'_, NetInterface<'_>> {
+        Box::new(self.net_dev.iter())
+    }
+
+    fn refresh_thermal(&mut self) -> Result<()> {
+        let files = crate::process::read_files(&thermal::SYS_THERMAL);
+        let topology: Vec<(usize, CpuTopology)> = slist::iter(&self.cpus)
+            .enumerate()
+            .filter_map(|(id, cpu)| Some((id, cpu.topology?)))
+            .collect();
+        self.thermal.refresh(&files, &topology);
+        Ok(())
+    }
+
+    fn iter_sensors(&self) -> BoxMiniIterator<'_, Sensor<'_>> {
+        Box::new(self.thermal.iter_sensors())
+    }
+
+    fn iter_cpu_temp(&self) -> BoxMiniIterator<'_, Option<f64>> {
+        Box::new(self.thermal.iter_cpu_temps())
+    }
+
+    fn refresh_processes(&mut self) -> Result<()> {
+        let stats = crate::process::spawn_and_get_output(processes::CAT_PROC_PID_STAT);
+        // `comm` may be in any encoding
+        let stats = String::from_utf8_lossy(&stats);
+        self.processes.refresh(&stats, std::time::Instant::now())
+    }
+
+    fn top_processes(&self, count: usize) -> Vec<ProcessInfo<'_>> {
+        self.processes.top(count)
+    }
+
+    fn iter_panes(&self, server_pid: u32) -> BoxMiniIterator<'_, ProcessInfo<'_>> {
+        Box::new(self.processes.pane_trees(server_pid).into_iter())
+    }
+
+    fn session_usage(&self) -> Option<f64> {
+        // The proxy is a child of the Zellij server
+        self.processes.session_usage(crate::process::proxy_pid())
+    }
+
+    fn refresh_disks(&mut self) -> Result<()> {
+        let files =
+            crate::process::read_files(&[diskstats::PROC_DISKSTATS, diskstats::SYS_BLOCK_DEV]);
+        self.disk_stats.refresh(&files, std::time::Instant::now())
+    }
+
+    fn iter_disks(&self) -> BoxMiniIterator<'_, DiskInfo<'_>> {
+        Box::new(self.disk_stats.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::System as _, *};
+    use std::time::{Duration, Instant};
+
+    /// A hand-made `/proc/stat` shaped like that of a KVM host with two CPUs
+    /// mostly running guests, one second apart (`USER_HZ = 100`). The `intr`
+    /// line is cut short; only its total matters. Unlike [`VM_GUEST_STAT`],
+    /// it isn't a capture, as none with guest time was at hand.
+    const KVM_HOST_STAT: [&str; 2] = [
+        "cpu  9334861 22697 2345909 83137959 47330 0 64455 0 7862154 18227
+cpu0 4705128 12856 1180562 41536714 24319 0 45601 0 3960312 10412
+cpu1 4629733 9841 1165347 41601245 23011 0 18854 0 3901842 7815
+intr 1125063498 27 9 0 0 0 0 0 0 1 0 0 0 156 0 0 0
+ctxt 2037713577
+btime 1659830400
+processes 2203482
+procs_running 3
+procs_blocked 0
+softirq 318224506 2 73155837 3 9187616 1291458 0 1436045 128736810 0 104416735
+",
+        "cpu  9334944 22699 2345922 83138057 47333 0 64456 0 7862224 18228
+cpu0 4705206 12858 1180572 41536722 24320 0 45602 0 3960382 10413
+cpu1 4629738 9841 1165350 41601335 23013 0 18854 0 3901842 7815
+intr 1125069153 27 9 0 0 0 0 0 0 1 0 0 0 156 0 0 0
+ctxt 2037741812
+btime 1659830400
+processes 2203490
+procs_running 2
+procs_blocked 0
+softirq 318227094 2 73156318 3 9187640 1291461 0 1436051 128737829 0 104417790
+",
+    ];
+
+    /// `/proc/stat` of a single-CPU Firecracker guest (Linux 6.18) under a
+    /// niced busy loop, read right before and after `top -b -n 2 -d 1`, whose
+    /// second report read `3.4 us, 6.9 sy, 89.7 ni, 0.0 id, 0.0 st`. The
+    /// `intr` lines are cut short.
+    const VM_GUEST_STAT: [&str; 2] = [
+        "cpu  104137 43 10711 345642 435 0 15 3950 0 0
+cpu0 104137 43 10711 345642 435 0 15 3950 0 0
+intr 506183 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0
+ctxt 986230
+btime 1792200390
+processes 24729
+procs_running 3
+procs_blocked 0
+softirq 223983 0 101024 5 19730 0 0 32 0 16 103176
+",
+        "cpu  104141 147 10719 345642 435 0 15 3950 0 0
+cpu0 104141 147 10719 345642 435 0 15 3950 0 0
+intr 506574 0 0 0 0 0 0 0 0 0 0 0 0 0 0 0
+ctxt 987644
+btime 1792200390
+processes 24879
+procs_running 2
+procs_blocked 0
+softirq 224572 0 101299 5 19730 0 0 32 0 16 103490
+",
+    ];
+
+    /// Feed `stats` taken one second apart.
+    fn system_with_stats(stats: &[&str]) -> System {
+        let mut system = System::default();
+        let t0 = Instant::now();
+        for (i, stat) in stats.iter().enumerate() {
+            system
+                .refresh_cpus_from(
+                    &Files::from_entries(&[(PROC_STAT, stat)]),
+                    t0 + Duration::from_secs(i as u64),
+                )
+                .unwrap();
+        }
+        system
+    }
+
+    #[test]
+    fn kvm_host() {
+        // cpu0: us 78 (incl. 70 guest), ni 2 (incl. 1 guest), sy 10, id 8,
+        //       wa 1, si 1
+        // cpu1: us 5, sy 3, id 90, wa 2
+        let system = system_with_stats(&KVM_HOST_STAT);
+        let usage: Vec<_> = system.iter_cpu_usage().collect();
+        assert_eq!(usage, [Some(0.91), Some(0.08)]);
+
+        let times: Vec<_> = system.iter_cpu_times().map(Option::unwrap).collect();
+        assert_eq!(times[0][CpuTime::User], 0.78);
+        assert_eq!(times[0][CpuTime::Nice], 0.02);
+        assert_eq!(times[0][CpuTime::System], 0.10);
+        assert_eq!(times[1][CpuTime::Iowait], 0.02);
+
+        assert_eq!(
+            system.activity(),
+            Some(ActivityRates {
+                context_switches: 28235.0,
+                interrupts: 5655.0,
+                softirqs: 2588.0,
+                forks: 8.0,
+            })
+        );
+    }
+
+    #[test]
+    fn vm_guest() {
+        // Rounded like `top`
+        let percent = |fraction: f64| (fraction * 1000.0).round() / 10.0;
+        let system = system_with_stats(&VM_GUEST_STAT);
+        let usage = system.iter_cpu_usage().next().unwrap().unwrap();
+        assert_eq!(percent(usage), 100.0);
+
+        let times = system.iter_cpu_times().next().unwrap().unwrap();
+        assert_eq!(percent(times[CpuTime::User]), 3.4);
+        assert_eq!(percent(times[CpuTime::System]), 6.9);
+        assert_eq!(percent(times[CpuTime::Nice]), 89.7);
+        assert_eq!(percent(times[CpuTime::Iowait]), 0.0);
+        assert_eq!(percent(times[CpuTime::Steal]), 0.0);
+    }
+
+    #[test]
+    fn column_count() {
+        // Linux < 2.5.41
+        let stats = CpuStats::parse("10 20 30 40").unwrap();
+        assert_eq!((stats.total, stats.active), (100, 60));
+
+        // Linux < 2.6.11
+        let stats = CpuStats::parse("10 20 30 40 50 60 70").unwrap();
+        assert_eq!((stats.total, stats.active), (280, 190));
+
+        // A hypothetical future kernel
+        let stats = CpuStats::parse("10 20 30 40 50 60 70 80 5 5 1000").unwrap();
+        assert_eq!((stats.total, stats.active), (360, 270));
+
+        assert!(CpuStats::parse("10 20 30").is_err());
+        assert!(CpuStats::parse("10 20 30 forty").is_err());
+    }
+
+    #[test]
+    fn unknown_usage() {
+        // No previous sample
+        let system = system_with_stats(&KVM_HOST_STAT[..1]);
+        let usage: Vec<_> = system.iter_cpu_usage().collect();
+        assert_eq!(usage, [None, None]);
+
+        // No time has elapsed
+        let system = system_with_stats(&[KVM_HOST_STAT[0], KVM_HOST_STAT[0]]);
+        let usage: Vec<_> = system.iter_cpu_usage().collect();
+        assert_eq!(usage, [None, None]);
+
+        // cpu1 was reset by hotplug
+        let system = system_with_stats(&[
+            KVM_HOST_STAT[0],
+            "cpu0 4705206 12858 1180572 41536722 24320 0 45602 0 3960382 10413\n\
+            cpu1 5 0 3 90 2 0 0 0 0 0\n",
+        ]);
+        let usage: Vec<_> = system.iter_cpu_usage().collect();
+        assert_eq!(usage, [Some(0.91), None]);
+
+        // A reset interval doesn't affect the next one
+        let system = system_with_stats(&[
+            KVM_HOST_STAT[0],
+            "cpu0 0 0 0 0\ncpu1 0 0 0 0\n",
+            "cpu0 50 0 0 50\ncpu1 0 0 0 100\n",
+        ]);
+        let usage: Vec<_> = system.iter_cpu_usage().collect();
+        assert_eq!(usage, [Some(0.5), Some(0.0)]);
+    }
+
+    #[test]
+    fn hotplug() {
+        let mut system = System::default();
+        let refresh = |system: &mut System, stat, online| {
+            system
+                .refresh_cpus_from(
+                    &Files::from_entries(&[
+                        (PROC_STAT, stat),
+                        (SYS_CPU_ONLINE, online),
+                        (SYS_CPU_PRESENT, "0-3\n"),
+                    ]),
+                    Instant::now(),
+                )
+                .unwrap();
+        };
+        refresh(
+            &mut system,
+            "cpu0 0 0 0 0\ncpu1 0 0 0 0\ncpu2 0 0 0 0\ncpu3 0 0 0 0\n",
+            "0-3\n",
+        );
+        // cpu1 goes offline
+        refresh(
+            &mut system,
+            "cpu0 10 0 0 90\ncpu2 20 0 0 80\ncpu3 30 0 0 70\n",
+            "0,2-3\n",
+        );
+
+        let usage: Vec<_> = system.iter_cpu_usage().collect();
+        assert_eq!(usage, [Some(0.1), None, Some(0.2), Some(0.3)]);
+        let online: Vec<_> = system.iter_cpu_info().map(|info| info.online).collect();
+        assert_eq!(online, [true, false, true, true]);
+
+        // cpu1 comes back online with reset counters
+        refresh(
+            &mut system,
+            "cpu0 20 0 0 180\ncpu1 0 0 0 5\ncpu2 40 0 0 160\ncpu3 60 0 0 140\n",
+            "0-3\n",
+        );
+        let usage: Vec<_> = system.iter_cpu_usage().collect();
+        assert_eq!(usage, [Some(0.1), None, Some(0.2), Some(0.3)]);
+        let online: Vec<_> = system.iter_cpu_info().map(|info| info.online).collect();
+        assert_eq!(online, [true; 4]);
+    }
+
+    #[test]
+    fn allowed() {
+        let mut system = System::default();
+        system
+            .refresh_cpus_from(
+                &Files::from_entries(&[
+                    (PROC_STAT, "cpu0 0 0 0 0\ncpu1 0 0 0 0\ncpu2 0 0 0 0\n"),
+                    (
+                        PROC_SELF_STATUS,
+                        "Name:\tcat\nCpus_allowed:\t5\nCpus_allowed_list:\t0,2\n",
+                    ),
+                ]),
+                Instant::now(),
+            )
+            .unwrap();
+        let allowed: Vec<_> = system.iter_cpu_info().map(|info| info.allowed).collect();
+        assert_eq!(allowed, [true, false, true]);
+
+        // Unknown affinity
+        let system = system_with_stats(&KVM_HOST_STAT);
+        let allowed: Vec<_> = system.iter_cpu_info().map(|info| info.allowed).collect();
+        assert_eq!(allowed, [true; 2]);
+    }
+
+    #[test]
+    fn isolated() {
+        let mut system = System::default();
+        system
+            .refresh_cpus_from(
+                &Files::from_entries(&[
+                    (PROC_STAT, "cpu0 0 0 0 0\ncpu1 0 0 0 0\ncpu2 0 0 0 0\n"),
+                    (SYS_CPU_ISOLATED, "1-2\n"),
+                    (SYS_CPU_NOHZ_FULL, "2\n"),
+                ]),
+                Instant::now(),
+            )
+            .unwrap();
+        let flags: Vec<_> = system
+            .iter_cpu_info()
+            .map(|info| (info.isolated, info.nohz_full))
+            .collect();
+        assert_eq!(flags, [(false, false), (true, false), (true, true)]);
+
+        // `nohz_full` unset on an older kernel
+        system
+            .refresh_cpus_from(
+                &Files::from_entries(&[
+                    (PROC_STAT, "cpu0 0 0 0 0\ncpu1 0 0 0 0\ncpu2 0 0 0 0\n"),
+                    (SYS_CPU_ISOLATED, "\n"),
+                    (SYS_CPU_NOHZ_FULL, "(null)\n"),
+                ]),
+                Instant::now(),
+            )
+            .unwrap();
+        assert!(system
+            .iter_cpu_info()
+            .all(|info| !info.isolated && !info.nohz_full));
+    }
+}
@@ -0,0 +1,155 @@
+//! Network interface statistics from `/proc/net/dev`
+use anyhow::{Context, Result};
+use std::time::Instant;
+
+use crate::sysinfo::{NetInterface, NetRates};
+
+#[derive(Debug, Default)]
+pub struct NetDev {
+    /// The time of the last sample
+    last_time: Option<Instant>,
+    interfaces: Vec<Interface>,
+}
+
+#[derive(Debug)]
+struct Interface {
+    name: String,
+    counters: Counters,
+    /// `None` if unknown
+    rates: Option<NetRates>,
+}
+
+/// `(rx_bytes, rx_packets, tx_bytes, tx_packets)`
+type Counters = [u64; 4];
+
+impl NetDev {
+    pub fn refresh(&mut self, net_dev: &str, now: Instant) -> Result<()> {
+        let elapsed = self
+            .last_time
+            .map(|last_time| now.saturating_duration_since(last_time).as_secs_f64())
+            .filter(|&elapsed| elapsed > 0.0);
+
+        // The first two lines are headers
+        let interfaces = net_dev
+            .lines()
+            .skip(2)
+            .map(|line| {
+                let (name, counters) =
+                    parse_line(line).with_context(|| format!("failed to parse line '{line}'"))?;
+                let last = self.interfaces.iter().find(|i| i.name == name);
+                let rates = last.zip(elapsed).and_then(|(last, elapsed)| {
+                    let mut rates = [0.0; 4];
+                    for ((rate, new), old) in rates.iter_mut().zip(counters).zip(last.counters) {
+                        // Fails if the interface was re-created
+                        *rate = new.checked_sub(old)? as f64 / elapsed;
+                    }
+                    Some(NetRates {
+                        rx_bytes: rates[0],
+                        rx_packets: rates[1],
+                        tx_bytes: rates[2],
+                        tx_packets: rates[3],
+                    })
+                });
+                Ok(Interface {
+                    name: name.to_owned(),
+                    counters,
+                    rates,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        self.interfaces = interfaces;
+        self.last_time = Some(now);
+        Ok(())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = NetInterface<'_>> {
+        self.interfaces.iter().map(|i| NetInterface {
+            name: &i.name,
+            rates: i.rates,
+        })
+    }
+}
+
+/// Parse a line like `  eth0: 138753555 6543 0 0 0 0 0 0 266688 3803 0 0 0 0 0 0`.
+fn parse_line(line: &str) -> Result<(&str, Counters)> {
+    let (name, fields) = line.split_once(':').context("':' is absent")?;
+    let fields = fields
+        .split_ascii_whitespace()
+        .map(str::parse::<u64>)
+        .collect::<Result<Vec<_>, _>>()?;
+    anyhow::ensure!(fields.len() >= 10, "too few columns");
+    Ok((name.trim(), [fields[0], fields[1], fields[8], fields[9]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const HEADER: &str = "\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+";
+
+    #[test]
+    fn refresh() {
+        let mut net_dev = NetDev::default();
+        let t0 = Instant::now();
+        net_dev
+            .refresh(
+                &format!(
+                    "{HEADER}    lo: 19463892    4796    0    0    0     0          0         0 19463892    4796    0    0    0     0       0          0
+  eth0: 138753555    6543    0    0    0     0          0         0   266688    3803    0    0    0     0       0          0
+"
+                ),
+                t0,
+            )
+            .unwrap();
+        assert_eq!(
+            net_dev.iter().map(|i| i.rates).collect::<Vec<_>>(),
+            [None, None]
+        );
+
+        net_dev
+            .refresh(
+                &format!(
+                    "{HEADER}    lo: 19463892    4796    0    0    0     0          0         0 19463892    4796    0    0    0     0       0          0
+  eth0: 140753555    7543    0    0    0     0          0         0   366688    3903    0    0    0     0       0          0
+ wlan0:     100       1    0    0    0     0          0         0      100       1    0    0    0     0       0          0
+"
+                ),
+                t0 + Duration::from_secs(2),
+            )
+            .unwrap();
+        let interfaces: Vec<_> = net_dev.iter().map(|i| (i.name, i.rates)).collect();
+        assert_eq!(
+            interfaces,
+            [
+                (
+                    "lo",
+                    Some(NetRates {
+                        rx_bytes: 0.0,
+                        rx_packets: 0.0,
+                        tx_bytes: 0.0,
+                        tx_packets: 0.0
+                    })
+                ),
+                (
+                    "eth0",
+                    Some(NetRates {
+                        rx_bytes: 1_000_000.0,
+                        rx_packets: 500.0,
+                        tx_bytes: 50_000.0,
+                        tx_packets: 50.0
+                    })
+                ),
+                ("wlan0", None),
+            ]
+        );
+
+        assert!(net_dev
+            .refresh(&format!("{HEADER}  eth0: 1 2 3\n"), t0)
+            .is_err());
+    }
+}
@@ -0,0 +1,128 @@
+//! Batteries from `/sys/class/power_supply`
+use super::{get_attr, group_by_dir};
+use crate::{
+    process::Files,
+    sysinfo::{BatteryInfo, BatteryStatus},
+};
+
+const SYS_POWER_SUPPLY_PREFIX: &str = "/sys/class/power_supply/";
+pub const SYS_POWER_SUPPLY: [&str; 5] = [
+    "/sys/class/power_supply/*/type",
+    "/sys/class/power_supply/*/capacity",
+    "/sys/class/power_supply/*/status",
+    "/sys/class/power_supply/*/power_now",
+    "/sys/class/power_supply/*/energy_now",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Battery {
+    name: String,
+    capacity: Option<f64>,
+    status: BatteryStatus,
+    power: Option<f64>,
+    energy: Option<f64>,
+}
+
+impl Battery {
+    pub fn info(&self) -> BatteryInfo<'_> {
+        BatteryInfo {
+            name: &self.name,
+            capacity: self.capacity,
+            status: self.status,
+            power: self.power,
+            energy: self.energy,
+        }
+    }
+}
+
+/// Get the power supplies of type `Battery`.
+pub fn parse(files: &Files) -> Vec<Battery> {
+    group_by_dir(files)
+        .into_iter()
+        .filter(|(_, attrs)| get_attr(attrs, "type") == Some("Battery"))
+        .map(|(dir, attrs)| {
+            // Parse an attribute that is absent on some models. Some firmware
+            // fails to respond (e.g., with `ENODATA` for `power_now`), which
+            // only makes that attribute unknown.
+            let parse = |name: &str| -> Option<f64> { get_attr(&attrs, name)?.parse().ok() };
+            Battery {
+                name: dir
+                    .strip_prefix(SYS_POWER_SUPPLY_PREFIX)
+                    .unwrap_or(dir)
+                    .to_owned(),
+                capacity: parse("capacity"),
+                status: match get_attr(&attrs, "status") {
+                    Some("Charging") => BatteryStatus::Charging,
+                    Some("Discharging") => BatteryStatus::Discharging,
+                    Some("Not charging") => BatteryStatus::NotCharging,
+                    Some("Full") => BatteryStatus::Full,
+                    _ => BatteryStatus::Unknown,
+                },
+                // µW → W
+                power: parse("power_now").map(|x| x / 1.0e6),
+                // µWh → Wh
+                energy: parse("energy_now").map(|x| x / 1.0e6),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_power_supply() {
+        let files = Files::from_entries(&[
+            ("/sys/class/power_supply/AC/type", "Mains\n"),
+            ("/sys/class/power_supply/BAT0/type", "Battery\n"),
+            ("/sys/class/power_supply/BAT1/type", "Battery\n"),
+            ("/sys/class/power_supply/BAT0/capacity", "85\n"),
+            ("/sys/class/power_supply/BAT1/capacity", "100\n"),
+            ("/sys/class/power_supply/BAT0/status", "Discharging\n"),
+            ("/sys/class/power_supply/BAT1/status", "Not charging\n"),
+            ("/sys/class/power_supply/BAT0/power_now", "7250000\n"),
+            ("/sys/class/power_supply/BAT0/energy_now", "42500000\n"),
+        ]);
+        let batteries: Vec<_> = parse(&files);
+        let batteries: Vec<_> = batteries.iter().map(Battery::info).collect();
+        assert_eq!(
+            batteries,
+            [
+                BatteryInfo {
+                    name: "BAT0",
+                    capacity: Some(85.0),
+                    status: BatteryStatus::Discharging,
+                    power: Some(7.25),
+                    energy: Some(42.5),
+                },
+                BatteryInfo {
+                    name: "BAT1",
+                    capacity: Some(100.0),
+                    status: BatteryStatus::NotCharging,
+                    power: None,
+                    energy: None,
+                },
+            ]
+        );
+
+        // Unusable attributes only affect their own fields
+        let files = Files::from_entries(&[
+            ("/sys/class/power_supply/BAT0/type", "Battery\n"),
+            ("/sys/class/power_supply/BAT0/capacity", "lots\n"),
+            ("/sys/class/power_supply/BAT0/status", "Charging\n"),
+            ("/sys/class/power_supply/BAT0/power_now", ""),
+            ("/sys/class/power_supply/BAT0/energy_now", "42500000\n"),
+        ]);
+        assert_eq!(
+            parse(&files)[0].info(),
+            BatteryInfo {
+                name: "BAT0",
+                capacity: None,
+                status: BatteryStatus::Charging,
+                power: None,
+                energy: Some(42.5),
+            }
+        );
+    }
+}
@@ -0,0 +1,407 @@
+//! Per-process CPU time from `/proc/[pid]/stat`
+use anyhow::{Context, Result};
+use std::time::Instant;
+
+use crate::sysinfo::ProcessInfo;
+
+/// Outputs every `/proc/[pid]/stat` in one process. Each of them is a line that
+/// starts with the PID. Processes may exit before `cat` reads them. The output
+/// is empty if `cat` couldn't start (e.g., with `E2BIG` because there are too
+/// many processes).
+pub const CAT_PROC_PID_STAT: &[u8] = b"cat /proc/[0-9]*/stat 2> /dev/null || true";
+
+/// The unit of `utime` and `stime`. `USER_HZ` is 100 on every architecture
+/// Linux supports, and we can't ask `sysconf` from the plugin VM anyway.
+const USER_HZ: f64 = 100.0;
+
+/// Guards against cycles in a process tree that changed while being read
+const MAX_TREE_DEPTH: usize = 1024;
+
+#[derive(Debug, Default)]
+pub struct Processes {
+    /// The time of the last sample
+    last_time: Option<Instant>,
+    /// The time between the last two samples, measured in seconds. `None` if
+    /// unknown.
+    elapsed: Option<f64>,
+    /// The last sample, sorted by PID
+    processes: Vec<Process>,
+    /// The sample before `processes`, sorted by PID
+    last_processes: Vec<Process>,
+}
+
+#[derive(Debug)]
+struct Process {
+    stat: Stat<String>,
+    /// Measured in CPUs. `None` if unknown.
+    usage: Option<f64>,
+}
+
+/// The fields of `/proc/[pid]/stat` we are interested in
+#[derive(Debug, Clone, PartialEq)]
+struct Stat<S> {
+    pid: u32,
+    comm: S,
+    ppid: u32,
+    /// Zero if the process has no controlling terminal
+    tty_nr: i64,
+    /// Distinguishes processes that reused a PID
+    start_time: u64,
+    /// `utime + stime`, measured in clock ticks
+    cpu_time: u64,
+    /// `cutime + cstime` (the time of the descendants that exited and were
+    /// waited for), measured in clock ticks
+    children_time: u64,
+}
+
+impl Stat<&str> {
+    fn into_owned(self) -> Stat<String> {
+        Stat {
+            pid: self.pid,
+            comm: self.comm.to_owned(),
+            ppid: self.ppid,
+            tty_nr: self.tty_nr,
+            start_time: self.start_time,
+            cpu_time: self.cpu_time,
+            children_time: self.children_time,
+        }
+    }
+}
+
+impl Processes {
+    /// Take a sample from the output of [`CAT_PROC_PID_STAT`].
+    pub fn refresh(&mut self, stats: &str, now: Instant) -> Result<()> {
+        let elapsed = self
+            .last_time
+            .map(|last_time| now.saturating_duration_since(last_time).as_secs_f64())
+            .filter(|&elapsed| elapsed > 0.0);
+
+        let mut processes: Vec<Process> = stats
+            .lines()
+            // A `comm` containing a newline splits its line. Skip the pieces
+            // rather than letting one process break the whole sample.
+            .filter_map(|line| parse(line).ok())
+            .map(|stat| {
+                let usage = self
+                    .processes
+                    .binary_search_by_key(&stat.pid, |p| p.stat.pid)
+                    .ok()
+                    .map(|i| &self.processes[i].stat)
+                    .filter(|last| last.start_time == stat.start_time)
+                    .zip(elapsed)
+                    .and_then(|(last, elapsed)| {
+                        let delta = stat.cpu_time.checked_sub(last.cpu_time)?;
+                        Some(delta as f64 / USER_HZ / elapsed)
+                    });
+                Process {
+                    stat: stat.into_owned(),
+                    usage,
+                }
+            })
+            .collect();
+        // There's at least `cat` itself
+        anyhow::ensure!(!processes.is_empty(), "failed to read /proc/[pid]/stat");
+        processes.sort_unstable_by_key(|p| p.stat.pid);
+
+        self.last_processes = std::mem::replace(&mut self.processes, processes);
+        self.elapsed = elapsed;
+        self.last_time = Some(now);
+        Ok(())
+    }
+
+    /// Get up to `count` processes with known usage in the descending order of
+    /// usage.
+    pub fn top(&self, count: usize) -> Vec<ProcessInfo<'_>> {
+        let mut processes: Vec<ProcessInfo<'_>> = self
+            .processes
+            .iter()
+            .filter_map(|p| {
+                Some(ProcessInfo {
+                    pid: p.stat.pid,
+                    name: &p.stat.comm,
+                    usage: p.usage?,
+                })
+            })
+            .collect();
+        processes.sort_by(|a, b| {
+            b.usage
+                .partial_cmp(&a.usage)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        processes.truncate(count);
+        processes
+    }
+
+    /// Get the panes of the Zellij server `server_pid`, i.e., its children
+    /// with a controlling terminal, in the order of PIDs, each with the usage
+    /// of its whole subtree. Returns nothing if the usage is unknown.
+    pub fn pane_trees(&self, server_pid: u32) -> Vec<ProcessInfo<'_>> {
+        let is_pane = |p: &Stat<String>| p.ppid == server_pid && p.tty_nr != 0;
+        self.tree_usages(is_pane)
+            .into_iter()
+            .map(|(pane, usage)| ProcessInfo {
+                pid: pane.pid,
+                name: &pane.comm,
+                usage,
+            })
+            .collect()
+    }
+
+    /// Get the usage of the whole process tree of the Zellij server that is
+    /// the parent of `child_pid`. Returns `None` if it's unknown.
+    pub fn session_usage(&self, child_pid: u32) -> Option<f64> {
+        let server_pid = find(&self.processes, child_pid)?.stat.ppid;
+        let (_, usage) = *self.tree_usages(|p| p.pid == server_pid).first()?;
+        Some(usage)
+    }
+
+    /// Get the usage of the subtree of each process matching `is_root`, in the
+    /// order of PIDs. Returns nothing if the usage is unknown.
+    fn tree_usages(&self, is_root: impl Fn(&Stat<String>) -> bool) -> Vec<(&Stat<String>, f64)> {
+        let elapsed = match self.elapsed {
+            Some(elapsed) => elapsed,
+            None => return Vec::new(),
+        };
+        let last_totals = tree_totals(&self.last_processes, &is_root);
+        tree_totals(&self.processes, &is_root)
+            .into_iter()
+            .map(|(root, total)| {
+                // A new tree started during the interval, so all of its time
+                // counts
+                let last_total = last_totals
+                    .iter()
+                    .find(|(last, _)| last.pid == root.pid && last.start_time == root.start_time)
+                    .map_or(0, |&(_, total)| total);
+                // Descendants that left the subtree (e.g., by being
+                // reparented to init) may make the total go backwards
+                let usage = total.saturating_sub(last_total) as f64 / USER_HZ / elapsed;
+                (root, usage)
+            })
+            .collect()
+    }
+}
+
+/// Find a process in `processes`, which is sorted by PID.
+fn find(processes: &[Process], pid: u32) -> Option<&Process> {
+    processes
+        .binary_search_by_key(&pid, |p| p.stat.pid)
+        .ok()
+        .map(|i| &processes[i])
+}
+
+/// Sum `cpu_time` and `children_time` over the subtree of each process
+/// matching `is_root` in `processes`, which is sorted by PID. Counting
+/// `children_time` keeps the total from dropping when descendants exit.
+fn tree_totals(
+    processes: &[Process],
+    is_root: impl Fn(&Stat<String>) -> bool,
+) -> Vec<(&Stat<String>, u64)> {
+    let mut roots: Vec<(&Stat<String>, u64)> = processes
+        .iter()
+        .filter(|p| is_root(&p.stat))
+        .map(|p| (&p.stat, 0))
+        .collect();
+    if roots.is_empty() {
+        return roots;
+    }
+
+    for process in processes {
+        let mut ancestor = process;
+        for _ in 0..MAX_TREE_DEPTH {
+            if is_root(&ancestor.stat) {
+                if let Some(root) = roots.iter_mut().find(|(r, _)| r.pid == ancestor.stat.pid) {
+                    root.1 += process.stat.cpu_time + process.stat.children_time;
+                }
+                break;
+            }
+            ancestor = match find(processes, ancestor.stat.ppid) {
+                Some(parent) => parent,
+                None => break,
+            };
+        }
+    }
+    roots
+}
+
+/// Parse `/proc/[pid]/stat`.
+fn parse(stat: &str) -> Result<Stat<&str>> {
+    // `comm` may contain spaces and parentheses, so look for the last `)`
+    let (pid, rest) = stat.split_once(" (").context("'comm' is absent")?;
+    let (comm, fields) = rest.rsplit_once(") ").context("'comm' is unterminated")?;
+    let pid = pid.parse().context("invalid PID")?;
+    // `state ppid pgrp session tty_nr tpgid flags minflt cminflt majflt
+    // cmajflt utime stime cutime cstime priority nice num_threads
+    // itrealvalue starttime ...`
+    let fields: Vec<&str> = fields.split_ascii_whitespace().collect();
+    anyhow::ensure!(fields.len() >= 20, "too few columns");
+    fn field<T: std::str::FromStr>(fields: &[&str], i: usize) -> Result<T> {
+        fields[i]
+            .parse()
+            .ok()
+            .with_context(|| format!("invalid column '{}'", fields[i]))
+    }
+    Ok(Stat {
+        pid,
+        comm,
+        ppid: field(&fields, 1)?,
+        tty_nr: field(&fields, 4)?,
+        start_time: field(&fields, 19)?,
+        cpu_time: field::<u64>(&fields, 11)? + field::<u64>(&fields, 12)?,
+        children_time: field::<u64>(&fields, 13)? + field::<u64>(&fields, 14)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// `(pid, comm, ppid, tty_nr, starttime, utime, cutime)`
+    type Sample<'a> = (u32, &'a str, u32, i64, u64, u64, u64);
+
+    /// Format `samples` like the output of [`CAT_PROC_PID_STAT`].
+    fn stats(samples: &[Sample<'_>]) -> String {
+        samples
+            .iter()
+            .map(|&(pid, comm, ppid, tty_nr, start_time, utime, cutime)| {
+                format!(
+                    "{pid} ({comm}) S {ppid} {pid} {pid} {tty_nr} -1 4194560 1017 0 0 0 \
+                    {utime} 0 {cutime} 0 20 0 1 0 {start_time} 22908928 2806 \
+                    18446744073709551615 0 0 0 0 0 0 0 0 0 0 0 0 17 3 0 0 0 0 0\n"
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn refresh() {
+        let mut processes = Processes::default();
+        let t0 = Instant::now();
+        processes
+            .refresh(
+                &stats(&[
+                    (1, "systemd", 0, 0, 1, 100, 0),
+                    (42, "cargo", 1, 0, 1000, 110, 0),
+                    (700, "Web Content (2)", 1, 0, 2000, 0, 0),
+                    (1000, "sleep", 1, 0, 3000, 0, 0),
+                    // Can't be told apart from the next line, so it's skipped
+                    (1001, "a\nb", 1, 0, 3000, 0, 0),
+                ]),
+                t0,
+            )
+            .unwrap();
+        assert_eq!(processes.top(3), []);
+
+        processes
+            .refresh(
+                &stats(&[
+                    (1, "systemd", 0, 0, 1, 101, 0),
+                    (42, "cargo", 1, 0, 1000, 270, 0),
+                    (700, "Web Content (2)", 1, 0, 2000, 50, 0),
+                    // Exited and another process took its PID
+                    (1000, "sh", 1, 0, 5000, 90, 0),
+                    (1001, "a\nb", 1, 0, 3000, 500, 0),
+                ]),
+                t0 + Duration::from_secs(2),
+            )
+            .unwrap();
+        assert_eq!(
+            processes.top(2),
+            [
+                ProcessInfo {
+                    pid: 42,
+                    name: "cargo",
+                    usage: 0.8,
+                },
+                ProcessInfo {
+                    pid: 700,
+                    name: "Web Content (2)",
+                    usage: 0.25,
+                },
+            ]
+        );
+        assert_eq!(processes.top(10).len(), 3);
+
+        // `cat` failed to start
+        assert!(processes.refresh("", t0 + Duration::from_secs(3)).is_err());
+    }
+
+    #[test]
+    fn trees() {
+        let mut processes = Processes::default();
+        let t0 = Instant::now();
+        let tty = 34816;
+        processes
+            .refresh(
+                &stats(&[
+                    (100, "zellij", 1, 0, 10, 500, 0),
+                    // Panes
+                    (200, "bash", 100, tty, 20, 10, 0),
+                    (300, "fish", 100, tty + 1, 30, 10, 0),
+                    // Not a pane (e.g., spawned by a plugin)
+                    (400, "sh", 100, 0, 40, 10, 0),
+                    (201, "make", 200, tty, 50, 0, 100),
+                    (202, "cc1", 201, tty, 60, 100, 0),
+                    (401, "cat", 400, 0, 70, 0, 0),
+                ]),
+                t0,
+            )
+            .unwrap();
+        assert_eq!(processes.pane_trees(100), []);
+        assert_eq!(processes.session_usage(400), None);
+
+        processes
+            .refresh(
+                &stats(&[
+                    (100, "zellij", 1, 0, 10, 600, 0),
+                    (200, "bash", 100, tty, 20, 10, 0),
+                    (300, "fish", 100, tty + 1, 30, 20, 0),
+                    (400, "sh", 100, 0, 40, 10, 0),
+                    // `cc1` (PID 202) exited after using 30 more ticks and
+                    // was waited for by `make`. Another one started.
+                    (201, "make", 200, tty, 50, 0, 230),
+                    (203, "cc1", 201, tty, 80, 40, 0),
+                    (401, "cat", 400, 0, 70, 50, 0),
+                ]),
+                t0 + Duration::from_secs(1),
+            )
+            .unwrap();
+        assert_eq!(
+            processes.pane_trees(100),
+            [
+                ProcessInfo {
+                    pid: 200,
+                    name: "bash",
+                    usage: 0.7,
+                },
+                ProcessInfo {
+                    pid: 300,
+                    name: "fish",
+                    usage: 0.1,
+                },
+            ]
+        );
+        // Everything but `init`
+        assert_eq!(processes.session_usage(400), Some(2.3));
+        assert_eq!(processes.session_usage(999), None);
+    }
+
+    #[test]
+    fn parse_stat() {
+        let stat = "7 (a) (b) S 1 7 7 0 -1 4194560 1017 0 0 0 4 5 6 7 20 0 1 0 123 0 0\n";
+        assert_eq!(
+            parse(stat).unwrap(),
+            Stat {
+                pid: 7,
+                comm: "a) (b",
+                ppid: 1,
+                tty_nr: 0,
+                start_time: 123,
+                cpu_time: 9,
+                children_time: 13,
+            }
+        );
+        assert!(parse("7 (init) S 1 2 3\n").is_err());
+        assert!(parse("7 init S 1 2 3\n").is_err());
+    }
+}
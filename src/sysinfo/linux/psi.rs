@@ -0,0 +1,120 @@
+//! Pressure Stall Information (`/proc/pressure/*`)
+use anyhow::{Context, Result};
+
+use crate::{
+    process::Files,
+    sysinfo::{Pressure, PressureAvgs, PressureInfo},
+};
+
+pub const PROC_PRESSURE_CPU: &str = "/proc/pressure/cpu";
+pub const PROC_PRESSURE_MEMORY: &str = "/proc/pressure/memory";
+pub const PROC_PRESSURE_IO: &str = "/proc/pressure/io";
+
+/// Parse the PSI files. Returns `Ok(None)` if the kernel doesn't support PSI
+/// (it was added in Linux 4.20 and may be disabled by `psi=0`).
+pub fn parse(files: &Files) -> Result<Option<PressureInfo>> {
+    let parse_file = |path| {
+        files
+            .get(path)
+            .map(parse_pressure)
+            .transpose()
+            .with_context(|| format!("failed to parse '{path}'"))
+    };
+    let info = PressureInfo {
+        cpu: parse_file(PROC_PRESSURE_CPU)?,
+        memory: parse_file(PROC_PRESSURE_MEMORY)?,
+        io: parse_file(PROC_PRESSURE_IO)?,
+    };
+    Ok(Some(info).filter(|info| info.cpu.is_some() || info.memory.is_some() || info.io.is_some()))
+}
+
+/// Parse a file like:
+///
+/// ```text
+/// some avg10=0.00 avg60=0.00 avg300=0.00 total=0
+/// full avg10=0.00 avg60=0.00 avg300=0.00 total=0
+/// ```
+fn parse_pressure(text: &str) -> Result<Pressure> {
+    let mut some = None;
+    let mut full = None;
+    for line in text.lines() {
+        let (kind, fields) = match line.split_once(' ') {
+            Some(x) => x,
+            None => continue,
+        };
+        let out = match kind {
+            "some" => &mut some,
+            "full" => &mut full,
+            _ => continue,
+        };
+        *out = Some(parse_avgs(fields).with_context(|| format!("failed to parse line '{line}'"))?);
+    }
+    Ok(Pressure {
+        some: some.context("'some' line is absent")?,
+        // CPU's `full` line was added in Linux 5.13
+        full,
+    })
+}
+
+fn parse_avgs(fields: &str) -> Result<PressureAvgs> {
+    let mut avgs = [None; 3];
+    for field in fields.split_ascii_whitespace() {
+        let (key, value) = field.split_once('=').context("'=' is absent")?;
+        let i = match key {
+            "avg10" => 0,
+            "avg60" => 1,
+            "avg300" => 2,
+            _ => continue,
+        };
+        avgs[i] = Some(value.parse()?);
+    }
+    Ok(PressureAvgs {
+        avg10: avgs[0].context("avg10 is absent")?,
+        avg60: avgs[1].context("avg60 is absent")?,
+        avg300: avgs[2].context("avg300 is absent")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_psi() {
+        let info = parse(&Files::from_entries(&[
+            (
+                PROC_PRESSURE_CPU,
+                "some avg10=12.50 avg60=3.25 avg300=0.75 total=123456789\n",
+            ),
+            (
+                PROC_PRESSURE_IO,
+                "some avg10=0.00 avg60=0.10 avg300=0.20 total=1\n\
+                full avg10=0.00 avg60=0.05 avg300=0.10 total=1\n",
+            ),
+        ]))
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            info.cpu,
+            Some(Pressure {
+                some: PressureAvgs {
+                    avg10: 12.5,
+                    avg60: 3.25,
+                    avg300: 0.75
+                },
+                full: None,
+            })
+        );
+        assert_eq!(info.memory, None);
+        assert_eq!(info.io.unwrap().full.unwrap().avg300, 0.1);
+
+        // No PSI support
+        assert_eq!(parse(&Files::from_entries(&[])).unwrap(), None);
+
+        assert!(parse(&Files::from_entries(&[(
+            PROC_PRESSURE_CPU,
+            "some avg10=x\n"
+        )]))
+        .is_err());
+    }
+}
@@ -0,0 +1,141 @@
+//! Energy counters of Intel RAPL (Running Average Power Limit) from
+//! `/sys/class/powercap`
+use anyhow::{Context, Result};
+use std::time::Instant;
+
+use super::{get_attr, group_by_dir};
+use crate::{process::Files, sysinfo::PowerInfo};
+
+/// Matches both the package zones (`intel-rapl:N`) and their subzones
+/// (`intel-rapl:N:M`). `energy_uj` is readable only by root on Linux ≥ 5.10.
+pub const SYS_RAPL: [&str; 3] = [
+    "/sys/class/powercap/intel-rapl:*/name",
+    "/sys/class/powercap/intel-rapl:*/energy_uj",
+    "/sys/class/powercap/intel-rapl:*/max_energy_range_uj",
+];
+
+#[derive(Debug, Default)]
+pub struct Rapl {
+    /// The time of the last sample
+    last_time: Option<Instant>,
+    /// `(zone directory, energy_uj)` of the last sample
+    last_energies: Vec<(String, u64)>,
+    pub info: Option<PowerInfo>,
+}
+
+impl Rapl {
+    pub fn refresh(&mut self, files: &Files, now: Instant) -> Result<()> {
+        let elapsed = self
+            .last_time
+            .map(|last_time| now.saturating_duration_since(last_time).as_secs_f64())
+            .filter(|&elapsed| elapsed > 0.0);
+
+        let mut info = PowerInfo::default();
+        let mut energies = Vec::new();
+        for (dir, attrs) in group_by_dir(files) {
+            let (name, energy) = match (get_attr(&attrs, "name"), get_attr(&attrs, "energy_uj")) {
+                (Some(name), Some(energy)) => (name, energy),
+                _ => continue,
+            };
+            let energy: u64 = energy
+                .parse()
+                .with_context(|| format!("failed to parse '{dir}/energy_uj'"))?;
+            energies.push((dir.to_owned(), energy));
+
+            let field = if name.starts_with("package-") {
+                &mut info.package
+            } else if name == "core" {
+                &mut info.core
+            } else if name == "dram" {
+                &mut info.dram
+            } else {
+                continue;
+            };
+
+            let last_energy = self
+                .last_energies
+                .iter()
+                .find(|(d, _)| d == dir)
+                .map(|&(_, energy)| energy);
+            let (elapsed, last_energy) = match (elapsed, last_energy) {
+                (Some(x), Some(y)) => (x, y),
+                _ => continue,
+            };
+            let delta = if energy >= last_energy {
+                energy - last_energy
+            } else {
+                // The counter wrapped around from `max_energy` to zero
+                let max_energy: u64 = get_attr(&attrs, "max_energy_range_uj")
+                    .context("max_energy_range_uj is absent")?
+                    .parse()
+                    .with_context(|| format!("failed to parse '{dir}/max_energy_range_uj'"))?;
+                max_energy
+                    .saturating_sub(last_energy)
+                    .saturating_add(energy)
+                    .saturating_add(1)
+            };
+            *field.get_or_insert(0.0) += delta as f64 * 1.0e-6 / elapsed;
+        }
+
+        self.last_time = Some(now);
+        self.last_energies = energies;
+        self.info = Some(info);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn files(package: u64, core: u64) -> Files {
+        Files::from_entries(&[
+            ("/sys/class/powercap/intel-rapl:0/name", "package-0\n"),
+            ("/sys/class/powercap/intel-rapl:0:0/name", "core\n"),
+            ("/sys/class/powercap/intel-rapl:0:1/name", "uncore\n"),
+            (
+                "/sys/class/powercap/intel-rapl:0/energy_uj",
+                &format!("{package}\n"),
+            ),
+            (
+                "/sys/class/powercap/intel-rapl:0:0/energy_uj",
+                &format!("{core}\n"),
+            ),
+            ("/sys/class/powercap/intel-rapl:0:1/energy_uj", "1000\n"),
+            (
+                "/sys/class/powercap/intel-rapl:0/max_energy_range_uj",
+                "262143328850\n",
+            ),
+            (
+                "/sys/class/powercap/intel-rapl:0:0/max_energy_range_uj",
+                "262143328850\n",
+            ),
+            (
+                "/sys/class/powercap/intel-rapl:0:1/max_energy_range_uj",
+                "262143328850\n",
+            ),
+        ])
+    }
+
+    #[test]
+    fn refresh() {
+        let mut rapl = Rapl::default();
+        let t0 = Instant::now();
+        rapl.refresh(&files(262143000000, 1000), t0).unwrap();
+        assert_eq!(rapl.info, Some(PowerInfo::default()));
+
+        // The package counter wraps around after drawing 328851 µJ, and the
+        // cores take 12 of the package's 20 J
+        rapl.refresh(&files(19671149, 12001000), t0 + Duration::from_secs(2))
+            .unwrap();
+        assert_eq!(
+            rapl.info,
+            Some(PowerInfo {
+                package: Some(10.0),
+                core: Some(6.0),
+                dram: None,
+            })
+        );
+    }
+}
@@ -0,0 +1,161 @@
+//! Swap usage from `/proc/meminfo`, `/proc/vmstat`, and zram
+use anyhow::{Context, Result};
+use std::time::Instant;
+
+use super::meminfo::parse_kib;
+use crate::{
+    process::Files,
+    sysinfo::{SwapInfo, ZramInfo},
+};
+
+pub const PROC_MEMINFO: &str = "/proc/meminfo";
+pub const PROC_VMSTAT: &str = "/proc/vmstat";
+pub const SYS_ZRAM_MM_STAT: &str = "/sys/block/zram*/mm_stat";
+
+#[derive(Debug, Default)]
+pub struct Swap {
+    /// The time and `(pswpin, pswpout)` of the last sample
+    last_counters: Option<(Instant, (u64, u64))>,
+    pub info: Option<SwapInfo>,
+}
+
+impl Swap {
+    pub fn refresh(&mut self, files: &Files, now: Instant) -> Result<()> {
+        let meminfo = files
+            .get(PROC_MEMINFO)
+            .context("failed to read /proc/meminfo")?;
+        let vmstat = files
+            .get(PROC_VMSTAT)
+            .context("failed to read /proc/vmstat")?;
+
+        let mut info = SwapInfo::default();
+        for line in meminfo.lines() {
+            let (key, value) = match line.split_once(':') {
+                Some(x) => x,
+                None => continue,
+            };
+            let field = match key {
+                "SwapTotal" => &mut info.total,
+                "SwapFree" => &mut info.free,
+                _ => continue,
+            };
+            *field = parse_kib(value).with_context(|| format!("failed to parse line '{line}'"))?;
+        }
+
+        let counters = parse_vmstat_counters(vmstat)?;
+        if let Some((last_time, last_counters)) = self.last_counters {
+            let elapsed = now.saturating_duration_since(last_time).as_secs_f64();
+            let rate = |new: u64, old: u64| {
+                Some(new.checked_sub(old)? as f64 / elapsed).filter(|_| elapsed > 0.0)
+            };
+            info.pages_in_rate = rate(counters.0, last_counters.0);
+            info.pages_out_rate = rate(counters.1, last_counters.1);
+        }
+
+        for (path, mm_stat) in files.iter().filter(|(path, _)| path.ends_with("/mm_stat")) {
+            // `None` if the glob pattern didn't match anything
+            let mm_stat = match mm_stat {
+                Some(x) => x,
+                None => continue,
+            };
+            let zram =
+                parse_mm_stat(mm_stat).with_context(|| format!("failed to parse '{path}'"))?;
+            let sum = info.zram.get_or_insert_with(ZramInfo::default);
+            sum.orig_data_size += zram.orig_data_size;
+            sum.compr_data_size += zram.compr_data_size;
+            sum.mem_used_total += zram.mem_used_total;
+        }
+
+        self.last_counters = Some((now, counters));
+        self.info = Some(info);
+        Ok(())
+    }
+}
+
+/// Get `(pswpin, pswpout)` from `/proc/vmstat`.
+fn parse_vmstat_counters(vmstat: &str) -> Result<(u64, u64)> {
+    let (mut pswpin, mut pswpout) = (None, None);
+    for line in vmstat.lines() {
+        let (key, value) = match line.split_once(' ') {
+            Some(x) => x,
+            None => continue,
+        };
+        let field = match key {
+            "pswpin" => &mut pswpin,
+            "pswpout" => &mut pswpout,
+            _ => continue,
+        };
+        *field = Some(
+            value
+                .parse::<u64>()
+                .with_context(|| format!("failed to parse line '{line}'"))?,
+        );
+    }
+    Ok((
+        pswpin.context("pswpin is absent")?,
+        pswpout.context("pswpout is absent")?,
+    ))
+}
+
+/// Parse `/sys/block/zramN/mm_stat`.
+fn parse_mm_stat(mm_stat: &str) -> Result<ZramInfo> {
+    let mut columns = mm_stat.split_ascii_whitespace().map(str::parse::<u64>);
+    let mut next = || -> Result<u64> { Ok(columns.next().context("too few columns")??) };
+    Ok(ZramInfo {
+        orig_data_size: next()?,
+        compr_data_size: next()?,
+        mem_used_total: next()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn files(pswpin: u64, pswpout: u64) -> Files {
+        Files::from_entries(&[
+            (
+                PROC_MEMINFO,
+                "MemTotal:       16318412 kB\nSwapTotal:       8388604 kB\nSwapFree:        6291452 kB\n",
+            ),
+            (
+                PROC_VMSTAT,
+                &format!("pgpgout 3402768\npswpin {pswpin}\npswpout {pswpout}\npgalloc_dma 0\n"),
+            ),
+            (
+                "/sys/block/zram0/mm_stat",
+                "1073741824 268435456 285212672        0 301989888     1024      256        0        0\n",
+            ),
+            (
+                "/sys/block/zram1/mm_stat",
+                "1048576 524288 528384        0 528384        0        0        0        0\n",
+            ),
+        ])
+    }
+
+    #[test]
+    fn refresh() {
+        let mut swap = Swap::default();
+        let t0 = Instant::now();
+        swap.refresh(&files(1000, 2000), t0).unwrap();
+        let info = swap.info.unwrap();
+        assert_eq!(info.total, 8388604 * 1024);
+        assert_eq!(info.free, 6291452 * 1024);
+        assert_eq!(info.pages_in_rate, None);
+        assert_eq!(
+            info.zram,
+            Some(ZramInfo {
+                orig_data_size: 1073741824 + 1048576,
+                compr_data_size: 268435456 + 524288,
+                mem_used_total: 285212672 + 528384,
+            })
+        );
+
+        swap.refresh(&files(1100, 2400), t0 + Duration::from_secs(2))
+            .unwrap();
+        let info = swap.info.unwrap();
+        assert_eq!(info.pages_in_rate, Some(50.0));
+        assert_eq!(info.pages_out_rate, Some(200.0));
+    }
+}
@@ -0,0 +1,186 @@
+//! Temperature sensors (`/sys/class/thermal` and `/sys/class/hwmon`)
+use super::{get_attr, group_by_dir};
+use crate::{
+    process::Files,
+    sysinfo::{CpuTopology, Sensor},
+};
+
+const SYS_THERMAL_ZONE_PREFIX: &str = "/sys/class/thermal/thermal_zone";
+const SYS_HWMON_PREFIX: &str = "/sys/class/hwmon/hwmon";
+pub const SYS_THERMAL: [&str; 5] = [
+    "/sys/class/thermal/thermal_zone*/type",
+    "/sys/class/thermal/thermal_zone*/temp",
+    "/sys/class/hwmon/hwmon*/name",
+    "/sys/class/hwmon/hwmon*/temp*_label",
+    "/sys/class/hwmon/hwmon*/temp*_input",
+];
+
+#[derive(Debug, Default)]
+pub struct Thermal {
+    /// `(name, temperature)`
+    sensors: Vec<(String, f64)>,
+    /// Indexed by CPU ID
+    cpu_temps: Vec<Option<f64>>,
+}
+
+impl Thermal {
+    /// Refresh from the files matching [`SYS_THERMAL`]. `topology` maps the
+    /// core temperatures to CPUs and is sorted by CPU ID.
+    pub fn refresh(&mut self, files: &Files, topology: &[(usize, CpuTopology)]) {
+        let dirs = group_by_dir(files);
+
+        self.sensors.clear();
+        // `(package ID, core ID, temperature)` reported by `coretemp`
+        let mut core_temps: Vec<(u64, u64, f64)> = Vec::new();
+        let mut num_coretemps = 0;
+        for (dir, attrs) in dirs.iter() {
+            if dir.starts_with(SYS_THERMAL_ZONE_PREFIX) {
+                // Skip zones that fail to respond with a temperature
+                if let Some(temp) = get_attr(attrs, "temp").and_then(parse_millidegrees) {
+                    let name = get_attr(attrs, "type").unwrap_or("thermal_zone");
+                    self.sensors.push((name.to_owned(), temp));
+                }
+            } else if dir.starts_with(SYS_HWMON_PREFIX) {
+                let chip = get_attr(attrs, "name").unwrap_or("hwmon");
+                let mut package_id = None;
+                let mut cores = Vec::new();
+                for &(name, input) in attrs {
+                    let channel = match name
+                        .strip_prefix("temp")
+                        .and_then(|name| name.strip_suffix("_input"))
+                    {
+                        Some(x) => x,
+                        None => continue,
+                    };
+                    // Skip channels that fail to respond (e.g., with an
+                    // empty reading) and keep the others
+                    let temp = match parse_millidegrees(input) {
+                        Some(x) => x,
+                        None => continue,
+                    };
+                    let label = get_attr(attrs, &format!("temp{channel}_label"));
+                    self.sensors.push((
+                        match label {
+                            Some(label) => format!("{chip} {label}"),
+                            None => format!("{chip} temp{channel}"),
+                        },
+                        temp,
+                    ));
+
+                    let label = label.unwrap_or("");
+                    if let Some(id) = label.strip_prefix("Package id ") {
+                        package_id = id.parse().ok();
+                    } else if let Some(Ok(id)) = label.strip_prefix("Core ").map(str::parse) {
+                        cores.push((id, temp));
+                    }
+                }
+                if chip == "coretemp" {
+                    // `coretemp` registers one device per package. Assume they
+                    // are in order if the package sensor is missing.
+                    let package_id = package_id.unwrap_or(num_coretemps);
+                    num_coretemps += 1;
+                    core_temps.extend(
+                        cores
+                            .into_iter()
+                            .map(|(core_id, temp)| (package_id, core_id, temp)),
+                    );
+                }
+            }
+        }
+
+        // `coretemp` labels the sensors by core ID, which is shared by SMT
+        // siblings
+        self.cpu_temps.clear();
+        for &(id, topology) in topology {
+            let temp = core_temps
+                .iter()
+                .find(|&&(package_id, core_id, _)| {
+                    package_id == topology.package_id && core_id == topology.core_id
+                })
+                .map(|&(.., temp)| temp);
+            if self.cpu_temps.len() <= id {
+                self.cpu_temps.resize(id + 1, None);
+            }
+            self.cpu_temps[id] = temp;
+        }
+    }
+
+    pub fn iter_sensors(&self) -> impl Iterator<Item = Sensor<'_>> + '_ {
+        self.sensors
+            .iter()
+            .map(|(name, temp)| Sensor { name, temp: *temp })
+    }
+
+    pub fn iter_cpu_temps(&self) -> impl Iterator<Item = Option<f64>> + '_ {
+        self.cpu_temps.iter().copied()
+    }
+}
+
+/// Parse a temperature measured in millidegrees Celsius.
+fn parse_millidegrees(text: &str) -> Option<f64> {
+    Some(text.parse::<i64>().ok()? as f64 / 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refresh() {
+        let files = Files::from_entries(&[
+            ("/sys/class/thermal/thermal_zone0/type", "acpitz\n"),
+            ("/sys/class/thermal/thermal_zone1/type", "x86_pkg_temp\n"),
+            ("/sys/class/thermal/thermal_zone0/temp", "27800\n"),
+            ("/sys/class/thermal/thermal_zone1/temp", "52000\n"),
+            // Failed to respond
+            ("/sys/class/thermal/thermal_zone2/type", "iwlwifi_1\n"),
+            ("/sys/class/thermal/thermal_zone2/temp", ""),
+            ("/sys/class/hwmon/hwmon0/name", "acpitz\n"),
+            ("/sys/class/hwmon/hwmon1/name", "coretemp\n"),
+            ("/sys/class/hwmon/hwmon1/temp1_label", "Package id 0\n"),
+            ("/sys/class/hwmon/hwmon1/temp2_label", "Core 0\n"),
+            ("/sys/class/hwmon/hwmon1/temp6_label", "Core 4\n"),
+            ("/sys/class/hwmon/hwmon0/temp1_input", "27800\n"),
+            ("/sys/class/hwmon/hwmon1/temp1_input", "52000\n"),
+            ("/sys/class/hwmon/hwmon1/temp2_input", "48000\n"),
+            ("/sys/class/hwmon/hwmon1/temp6_input", "51000\n"),
+            ("/sys/class/hwmon/hwmon1/temp7_label", "Core 8\n"),
+            ("/sys/class/hwmon/hwmon1/temp7_input", ""),
+        ]);
+        let topology: Vec<_> = [(0, 0), (1, 4), (2, 0), (4, 8)]
+            .iter()
+            .map(|&(id, core_id)| {
+                let topology = CpuTopology {
+                    package_id: 0,
+                    cluster_id: None,
+                    core_id,
+                    first_sibling: id,
+                    core_type: None,
+                    node_id: None,
+                };
+                (id, topology)
+            })
+            .collect();
+        let mut thermal = Thermal::default();
+        thermal.refresh(&files, &topology);
+
+        let sensors: Vec<_> = thermal
+            .iter_sensors()
+            .map(|s| (s.name.to_owned(), s.temp))
+            .collect();
+        assert_eq!(
+            sensors,
+            [
+                ("acpitz".to_owned(), 27.8),
+                ("x86_pkg_temp".to_owned(), 52.0),
+                ("acpitz temp1".to_owned(), 27.8),
+                ("coretemp Package id 0".to_owned(), 52.0),
+                ("coretemp Core 0".to_owned(), 48.0),
+                ("coretemp Core 4".to_owned(), 51.0),
+            ]
+        );
+
+        let cpu_temps: Vec<_> = thermal.iter_cpu_temps().collect();
+        assert_eq!(cpu_temps, [Some(48.0), Some(51.0), Some(48.0), None, None]);
+    }
+}
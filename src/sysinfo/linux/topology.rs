@@ -0,0 +1,231 @@
+//! CPU topology (`/sys/devices/system/cpu/cpuN/topology`)
+use anyhow::{Context, Result};
+
+use super::cpulist::CpuSet;
+use crate::{
+    process::Files,
+    sysinfo::{CoreType, CpuTopology},
+};
+
+pub const SYS_CPU_TOPOLOGY: [&str; 2] = [
+    "/sys/devices/system/cpu/cpu[0-9]*/topology/core_id",
+    "/sys/devices/system/cpu/cpu[0-9]*/topology/physical_package_id",
+];
+/// Needed for [`CpuTopology::cluster_id`], [`CpuTopology::first_sibling`],
+/// [`CpuTopology::core_type`], and [`CpuTopology::node_id`]. `cluster_id`
+/// appeared in Linux 5.16.
+pub const SYS_CPU_TOPOLOGY_EXTRA: [&str; 6] = [
+    "/sys/devices/system/cpu/cpu[0-9]*/topology/cluster_id",
+    "/sys/devices/system/cpu/cpu[0-9]*/topology/thread_siblings_list",
+    SYS_CPU_CORE_CPUS,
+    SYS_CPU_ATOM_CPUS,
+    "/sys/devices/system/cpu/cpu[0-9]*/cpu_capacity",
+    "/sys/devices/system/node/node[0-9]*/cpulist",
+];
+const SYS_NODE_PREFIX: &str = "/sys/devices/system/node/node";
+/// The CPUs of each type on Intel hybrid processors
+const SYS_CPU_CORE_CPUS: &str = "/sys/devices/cpu_core/cpus";
+const SYS_CPU_ATOM_CPUS: &str = "/sys/devices/cpu_atom/cpus";
+
+/// Get the topology of each CPU that has both `core_id` and
+/// `physical_package_id`, sorted by CPU ID. The other attributes are filled
+/// in if they are in `files`.
+pub fn parse(files: &Files) -> Result<Vec<(usize, CpuTopology)>> {
+    // These may be `-1` if unknown
+    let ids = super::parse_cpu_attrs::<i64, 3>(
+        files,
+        "topology",
+        ["core_id", "physical_package_id", "cluster_id"],
+    )?;
+    let siblings =
+        super::parse_cpu_attrs::<CpuSet, 1>(files, "topology", ["thread_siblings_list"])?;
+    let unsigned = |id: Option<i64>| id.and_then(|id| u64::try_from(id).ok());
+
+    let parse_cpu_list = |path| {
+        files
+            .get(path)
+            .map(CpuSet::parse)
+            .transpose()
+            .with_context(|| format!("failed to parse '{path}'"))
+    };
+    let core_cpus = parse_cpu_list(SYS_CPU_CORE_CPUS)?;
+    let atom_cpus = parse_cpu_list(SYS_CPU_ATOM_CPUS)?;
+    // Fall back to `cpu_capacity` (e.g., on Arm big.LITTLE), which is
+    // normalized to 1024 for the most capable CPUs
+    let capacities = super::parse_cpu_attrs::<u64, 1>(files, "", ["cpu_capacity"])?;
+    let max_capacity = capacities.iter().filter_map(|&(_, [x])| x).max();
+    let min_capacity = capacities.iter().filter_map(|&(_, [x])| x).min();
+    let core_type = |id: usize| -> Option<CoreType> {
+        if core_cpus.is_some() || atom_cpus.is_some() {
+            if core_cpus.as_ref().map_or(false, |cpus| cpus.contains(id)) {
+                Some(CoreType::Performance)
+            } else if atom_cpus.as_ref().map_or(false, |cpus| cpus.contains(id)) {
+                Some(CoreType::Efficiency)
+            } else {
+                None
+            }
+        } else if min_capacity < max_capacity {
+            let i = capacities.binary_search_by_key(&id, |&(id, _)| id).ok()?;
+            let capacity = capacities[i].1[0]?;
+            Some(if Some(capacity) == max_capacity {
+                CoreType::Performance
+            } else {
+                CoreType::Efficiency
+            })
+        } else {
+            None
+        }
+    };
+
+    let nodes = files
+        .iter()
+        .filter_map(|(path, content)| {
+            let id = path
+                .strip_prefix(SYS_NODE_PREFIX)?
+                .strip_suffix("/cpulist")?;
+            Some((id.parse::<u64>().ok()?, path, content?))
+        })
+        .map(|(id, path, content)| {
+            let cpus =
+                CpuSet::parse(content).with_context(|| format!("failed to parse '{path}'"))?;
+            Ok((id, cpus))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let node_id = |id: usize| {
+        nodes
+            .iter()
+            .find(|(_, cpus)| cpus.contains(id))
+            .map(|&(node_id, _)| node_id)
+    };
+
+    Ok(ids
+        .into_iter()
+        .filter_map(|(id, [core_id, package_id, cluster_id])| {
+            let first_sibling = siblings
+                .binary_search_by_key(&id, |&(id, _)| id)
+                .ok()
+                .and_then(|i| siblings[i].1[0].as_ref()?.min())
+                .unwrap_or(id);
+            Some((
+                id,
+                CpuTopology {
+                    package_id: unsigned(package_id)?,
+                    cluster_id: unsigned(cluster_id),
+                    core_id: unsigned(core_id)?,
+                    first_sibling,
+                    core_type: core_type(id),
+                    node_id: node_id(id),
+                },
+            ))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_topology() {
+        let mut entries = Vec::new();
+        // Two packages with two cores each and SMT2. CPU IDs are assigned to
+        // the first threads first.
+        for cpu in 0..8 {
+            let package = cpu / 2 % 2;
+            let core = cpu % 2;
+            let path = |name| format!("/sys/devices/system/cpu/cpu{cpu}/topology/{name}");
+            entries.push((path("core_id"), format!("{core}\n")));
+            entries.push((path("physical_package_id"), format!("{package}\n")));
+            entries.push((path("cluster_id"), "-1\n".to_owned()));
+            entries.push((
+                path("thread_siblings_list"),
+                format!("{},{}\n", cpu % 4, cpu % 4 + 4),
+            ));
+        }
+        // One node per package
+        entries.push((
+            "/sys/devices/system/node/node0/cpulist".to_owned(),
+            "0-1,4-5\n".to_owned(),
+        ));
+        entries.push((
+            "/sys/devices/system/node/node1/cpulist".to_owned(),
+            "2-3,6-7\n".to_owned(),
+        ));
+        let entries: Vec<(&str, &str)> = entries
+            .iter()
+            .map(|(path, content)| (path.as_str(), content.as_str()))
+            .collect();
+
+        let topology = parse(&Files::from_entries(&entries)).unwrap();
+        assert_eq!(topology.len(), 8);
+        assert_eq!(topology[4].1.node_id, Some(0));
+        assert_eq!(
+            topology[6],
+            (
+                6,
+                CpuTopology {
+                    package_id: 1,
+                    cluster_id: None,
+                    core_id: 0,
+                    first_sibling: 2,
+                    core_type: None,
+                    node_id: Some(1),
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn core_type() {
+        let topology = |cpu: usize| {
+            [
+                (
+                    format!("/sys/devices/system/cpu/cpu{cpu}/topology/core_id"),
+                    format!("{cpu}\n"),
+                ),
+                (
+                    format!("/sys/devices/system/cpu/cpu{cpu}/topology/physical_package_id"),
+                    "0\n".to_owned(),
+                ),
+            ]
+        };
+        let core_types = |extra: &[(String, String)]| {
+            let entries: Vec<_> = (0..4).flat_map(topology).chain(extra.to_vec()).collect();
+            let entries: Vec<(&str, &str)> = entries
+                .iter()
+                .map(|(path, content)| (path.as_str(), content.as_str()))
+                .collect();
+            let topology = parse(&Files::from_entries(&entries)).unwrap();
+            topology
+                .iter()
+                .map(|(_, t)| t.core_type)
+                .collect::<Vec<_>>()
+        };
+        let (p, e) = (Some(CoreType::Performance), Some(CoreType::Efficiency));
+
+        // Intel hybrid
+        assert_eq!(
+            core_types(&[
+                (SYS_CPU_CORE_CPUS.to_owned(), "0-1\n".to_owned()),
+                (SYS_CPU_ATOM_CPUS.to_owned(), "2-3\n".to_owned()),
+            ]),
+            [p, p, e, e]
+        );
+
+        // Arm big.LITTLE
+        let capacities = ["1024", "1024", "446", "446"]
+            .iter()
+            .enumerate()
+            .map(|(cpu, capacity)| {
+                (
+                    format!("/sys/devices/system/cpu/cpu{cpu}/cpu_capacity"),
+                    format!("{capacity}\n"),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(core_types(&capacities), [p, p, e, e]);
+
+        // Homogeneous
+        assert_eq!(core_types(&[]), [None; 4]);
+    }
+}
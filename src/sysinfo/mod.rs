@@ -7,11 +7,561 @@ mod linux;
 pub trait System {
     fn refresh_cpus(&mut self) -> Result<()>;
     fn num_cpus(&self) -> usize;
-    fn iter_cpu_usage(&self) -> BoxMiniIterator<'_, f64>;
+    /// Get each CPU's usage during the last refresh interval. Yields `None`
+    /// for CPUs whose usage is unknown, e.g., because their counters were
+    /// reset.
+    fn iter_cpu_usage(&self) -> BoxMiniIterator<'_, Option<f64>>;
+    /// Get the breakdown of each CPU's time by [`CpuTime`] category. Yields
+    /// `None` under the same condition as [`Self::iter_cpu_usage`].
+    fn iter_cpu_times(&self) -> BoxMiniIterator<'_, Option<CpuTimes>>;
+    fn iter_cpu_info(&self) -> BoxMiniIterator<'_, CpuInfo>;
+    /// Refresh the values returned by [`Self::iter_cpu_topology`]. The
+    /// topology only changes when CPUs go online or offline.
+    fn refresh_topology(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Get each CPU's topology as of the last call to
+    /// [`Self::refresh_topology`]. Yields `None` if it's unknown for the CPU,
+    /// e.g., because it's offline. May end before covering all CPUs.
+    fn iter_cpu_topology(&self) -> BoxMiniIterator<'_, Option<CpuTopology>> {
+        Box::new(std::iter::empty())
+    }
+
+    /// Refresh the values returned by [`Self::iter_cpu_freq`].
+    fn refresh_cpu_freq(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Get each CPU's clock frequency as of the last call to
+    /// [`Self::refresh_cpu_freq`]. Yields `None` if it's unavailable for the
+    /// CPU. May end before covering all CPUs.
+    fn iter_cpu_freq(&self) -> BoxMiniIterator<'_, Option<CpuFreq>> {
+        Box::new(std::iter::empty())
+    }
+
+    /// Refresh the values returned by [`Self::iter_cpu_interrupts`].
+    fn refresh_interrupts(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Get each CPU's interrupt rates as of the last call to
+    /// [`Self::refresh_interrupts`]. Yields `None` if they're unknown for the
+    /// CPU. May end before covering all CPUs.
+    fn iter_cpu_interrupts(&self) -> BoxMiniIterator<'_, Option<InterruptRates>> {
+        Box::new(std::iter::empty())
+    }
+    /// Get the kernel activity rates as of the last call to
+    /// [`Self::refresh_cpus`]. Returns `None` if they're unknown.
+    fn activity(&self) -> Option<ActivityRates> {
+        None
+    }
+    /// Get the system load as of the last call to [`Self::refresh_cpus`].
+    /// Returns `None` if it's unavailable.
+    fn load(&self) -> Option<LoadInfo> {
+        None
+    }
+
+    /// Refresh the value returned by [`Self::memory`].
+    fn refresh_memory(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Get the memory usage as of the last call to [`Self::refresh_memory`].
+    /// Returns `None` if it's unavailable.
+    fn memory(&self) -> Option<MemoryInfo> {
+        None
+    }
+
+    /// Refresh the value returned by [`Self::swap`].
+    fn refresh_swap(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Get the swap usage as of the last call to [`Self::refresh_swap`].
+    /// Returns `None` if it's unavailable.
+    fn swap(&self) -> Option<SwapInfo> {
+        None
+    }
+
+    /// Refresh the value returned by [`Self::pressure`].
+    fn refresh_pressure(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Get the resource pressure as of the last call to
+    /// [`Self::refresh_pressure`]. Returns `None` if it's unavailable.
+    fn pressure(&self) -> Option<PressureInfo> {
+        None
+    }
+
+    /// Refresh the value returned by [`Self::power`].
+    fn refresh_power(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Get the power draw during the interval between the last two calls to
+    /// [`Self::refresh_power`]. Returns `None` if it's unavailable.
+    fn power(&self) -> Option<PowerInfo> {
+        None
+    }
+
+    /// Refresh the values returned by [`Self::iter_batteries`].
+    fn refresh_batteries(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Iterate over the batteries as of the last call to
+    /// [`Self::refresh_batteries`].
+    fn iter_batteries(&self) -> BoxMiniIterator<'_, BatteryInfo<'_>> {
+        Box::new(std::iter::empty())
+    }
+
+    /// Refresh the values returned by [`Self::iter_network`].
+    fn refresh_network(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Iterate over the network interfaces as of the last call to
+    /// [`Self::refresh_network`].
+    fn iter_network(&self) -> BoxMiniIterator<'_, NetInterface<'_>> {
+        Box::new(std::iter::empty())
+    }
+
+    /// Refresh the values returned by [`Self::iter_sensors`] and
+    /// [`Self::iter_cpu_temp`].
+    fn refresh_thermal(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Iterate over the temperature sensors as of the last call to
+    /// [`Self::refresh_thermal`].
+    fn iter_sensors(&self) -> BoxMiniIterator<'_, Sensor<'_>> {
+        Box::new(std::iter::empty())
+    }
+    /// Get the temperature of each CPU's core as of the last call to
+    /// [`Self::refresh_thermal`]. Yields `None` if it's unknown for the CPU,
+    /// including when [`Self::refresh_topology`] hasn't been called. May end
+    /// before covering all CPUs.
+    fn iter_cpu_temp(&self) -> BoxMiniIterator<'_, Option<f64>> {
+        Box::new(std::iter::empty())
+    }
+
+    /// Refresh the values returned by [`Self::top_processes`],
+    /// [`Self::iter_panes`], and [`Self::session_usage`].
+    fn refresh_processes(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Get up to `count` processes that used the most CPU time during the
+    /// last refresh interval, in the descending order of usage.
+    fn top_processes(&self, _count: usize) -> Vec<ProcessInfo<'_>> {
+        Vec::new()
+    }
+    /// Iterate over the terminal panes of the Zellij server `server_pid` (its
+    /// children with a controlling terminal) in the order of PIDs. The usage
+    /// covers each pane's whole process tree during the last refresh
+    /// interval, including descendants that exited in the meantime.
+    fn iter_panes(&self, _server_pid: u32) -> BoxMiniIterator<'_, ProcessInfo<'_>> {
+        Box::new(std::iter::empty())
+    }
+    /// Get the CPU usage of the whole process tree of the Zellij server
+    /// running this plugin during the last refresh interval, measured in CPUs.
+    /// Returns `None` if it's unknown.
+    fn session_usage(&self) -> Option<f64> {
+        None
+    }
+
+    /// Refresh the values returned by [`Self::iter_disks`].
+    fn refresh_disks(&mut self) -> Result<()> {
+        Ok(())
+    }
+    /// Iterate over the block devices as of the last call to
+    /// [`Self::refresh_disks`].
+    fn iter_disks(&self) -> BoxMiniIterator<'_, DiskInfo<'_>> {
+        Box::new(std::iter::empty())
+    }
+}
+
+/// Information about a CPU other than its usage
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CpuInfo {
+    /// The kernel's CPU number
+    pub id: usize,
+    pub online: bool,
+    /// Indicates whether the CPU affinity of Zellij (set by `taskset` or a
+    /// cgroup cpuset) includes this CPU
+    pub allowed: bool,
+    /// Indicates whether the `isolcpus` boot parameter removes this CPU from
+    /// general scheduling
+    pub isolated: bool,
+    /// Indicates whether the `nohz_full` boot parameter makes this CPU omit
+    /// scheduling-clock ticks
+    pub nohz_full: bool,
+}
+
+/// The location of a CPU in the hierarchy of packages, clusters, cores, and SMT
+/// threads, and in NUMA nodes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CpuTopology {
+    pub package_id: u64,
+    /// `None` if the kernel doesn't group cores into clusters
+    pub cluster_id: Option<u64>,
+    /// Unique within the package
+    pub core_id: u64,
+    /// The smallest CPU ID among this CPU's SMT siblings (including itself),
+    /// which is unique to the core within the system
+    pub first_sibling: usize,
+    /// `None` if it's unknown or all cores are of the same type
+    pub core_type: Option<CoreType>,
+    /// The NUMA node. `None` if the kernel doesn't expose NUMA nodes.
+    pub node_id: Option<u64>,
+}
+
+/// The class of a core in a heterogeneous processor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoreType {
+    /// A P-core (Intel) or a big core (Arm)
+    Performance,
+    /// An E-core (Intel) or a LITTLE core (Arm)
+    Efficiency,
+}
+
+/// The clock frequency of a CPU and its scaling bounds, measured in kHz
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CpuFreq {
+    pub cur: u64,
+    pub min: u64,
+    pub max: u64,
+}
+
+impl CpuFreq {
+    /// Get where the current frequency lies between the bounds, in range
+    /// `0.0..=1.0`.
+    pub fn fraction(&self) -> f64 {
+        if self.max <= self.min {
+            return 1.0;
+        }
+        let cur = self.cur.max(self.min).min(self.max);
+        (cur - self.min) as f64 / (self.max - self.min) as f64
+    }
+}
+
+/// The interrupts handled by a CPU, measured per second
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct InterruptRates {
+    /// Hardware interrupts (`/proc/interrupts`)
+    pub irqs: f64,
+    /// Softirqs (`/proc/softirqs`)
+    pub softirqs: f64,
+}
+
+/// System load and run queue length
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct LoadInfo {
+    /// The 1-, 5-, and 15-minute load averages
+    pub avg: [f64; 3],
+    /// The number of currently runnable tasks (`/proc/loadavg`)
+    pub runnable_tasks: u64,
+    /// The number of existing tasks (`/proc/loadavg`)
+    pub total_tasks: u64,
+    /// The number of runnable tasks (`procs_running` in `/proc/stat`)
+    pub procs_running: u64,
+    /// The number of tasks blocked on I/O (`procs_blocked` in `/proc/stat`)
+    pub procs_blocked: u64,
+}
+
+/// System-wide kernel activity (the counters in `/proc/stat`), measured per
+/// second
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ActivityRates {
+    /// Context switches (`ctxt`)
+    pub context_switches: f64,
+    /// Hardware interrupts (`intr`)
+    pub interrupts: f64,
+    /// Softirqs (`softirq`)
+    pub softirqs: f64,
+    /// Created processes and threads (`processes`)
+    pub forks: f64,
+}
+
+/// System memory usage, measured in bytes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryInfo {
+    pub total: u64,
+    /// The amount of memory available for new allocations without swapping
+    pub available: u64,
+    pub buffers: u64,
+    /// Page cache, including [`Self::shmem`]
+    pub cached: u64,
+    pub shmem: u64,
+    pub dirty: u64,
+}
+
+impl MemoryInfo {
+    /// Get the fraction of memory that is not available.
+    pub fn used_fraction(&self) -> f64 {
+        self.total.saturating_sub(self.available) as f64 / self.total as f64
+    }
+}
+
+/// Swap usage
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SwapInfo {
+    /// Measured in bytes
+    pub total: u64,
+    /// Measured in bytes
+    pub free: u64,
+    /// The sum over all zram devices. `None` if there are none.
+    pub zram: Option<ZramInfo>,
+    /// The number of pages swapped in per second during the last refresh
+    /// interval. `None` if it's unknown.
+    pub pages_in_rate: Option<f64>,
+    /// The number of pages swapped out per second during the last refresh
+    /// interval. `None` if it's unknown.
+    pub pages_out_rate: Option<f64>,
+}
+
+/// zram device usage, measured in bytes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ZramInfo {
+    /// The uncompressed size of the stored data
+    pub orig_data_size: u64,
+    /// The compressed size of the stored data
+    pub compr_data_size: u64,
+    /// The amount of memory consumed, including the allocator overhead
+    pub mem_used_total: u64,
+}
+
+/// Resource pressure (Pressure Stall Information). Each field is `None` if
+/// unavailable.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PressureInfo {
+    pub cpu: Option<Pressure>,
+    pub memory: Option<Pressure>,
+    pub io: Option<Pressure>,
+}
+
+/// The pressure on a resource
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Pressure {
+    /// The share of time in which at least some tasks were stalled
+    pub some: PressureAvgs,
+    /// The share of time in which all non-idle tasks were stalled
+    pub full: Option<PressureAvgs>,
+}
+
+/// Running averages of stall time, measured in percent
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PressureAvgs {
+    pub avg10: f64,
+    pub avg60: f64,
+    pub avg300: f64,
+}
+
+/// Power draw summed over all packages, measured in watts. Each field is `None`
+/// if it's unknown.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PowerInfo {
+    pub package: Option<f64>,
+    /// The cores, which are part of the package
+    pub core: Option<f64>,
+    pub dram: Option<f64>,
+}
+
+/// A battery. Each `Option` field is `None` if it's unknown.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatteryInfo<'a> {
+    pub name: &'a str,
+    /// The charge level, measured in percent
+    pub capacity: Option<f64>,
+    pub status: BatteryStatus,
+    /// The charge or discharge rate, measured in watts
+    pub power: Option<f64>,
+    /// The stored energy, measured in watt-hours
+    pub energy: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatteryStatus {
+    Unknown,
+    Charging,
+    Discharging,
+    /// Connected to a power source but not charging (e.g., because of a
+    /// charge threshold)
+    NotCharging,
+    Full,
+}
+
+/// A network interface
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NetInterface<'a> {
+    pub name: &'a str,
+    /// The throughput during the last refresh interval. `None` if it's
+    /// unknown.
+    pub rates: Option<NetRates>,
+}
+
+/// Network throughput, measured per second
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct NetRates {
+    pub rx_bytes: f64,
+    pub rx_packets: f64,
+    pub tx_bytes: f64,
+    pub tx_packets: f64,
+}
+
+impl std::ops::AddAssign for NetRates {
+    fn add_assign(&mut self, rhs: Self) {
+        self.rx_bytes += rhs.rx_bytes;
+        self.rx_packets += rhs.rx_packets;
+        self.tx_bytes += rhs.tx_bytes;
+        self.tx_packets += rhs.tx_packets;
+    }
+}
+
+/// A temperature sensor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sensor<'a> {
+    /// The thermal zone type or the hwmon chip name followed by the channel
+    /// label (e.g., `coretemp Core 0`)
+    pub name: &'a str,
+    /// Measured in degrees Celsius
+    pub temp: f64,
+}
+
+/// A block device
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiskInfo<'a> {
+    pub name: &'a str,
+    pub partition: bool,
+    /// The activity during the last refresh interval. `None` if it's unknown.
+    pub rates: Option<DiskRates>,
+}
+
+/// Block device activity
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct DiskRates {
+    /// Bytes read per second
+    pub read_bytes: f64,
+    /// Bytes written per second
+    pub write_bytes: f64,
+    /// The fraction of time in which the device had I/O requests in flight
+    pub utilization: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProcessInfo<'a> {
+    pub pid: u32,
+    /// The executable name truncated to 15 bytes (`comm`)
+    pub name: &'a str,
+    /// Measured in CPUs, e.g., `2.0` for a process keeping two CPUs busy
+    pub usage: f64,
+}
+
+/// A category of CPU time as accounted for by the kernel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuTime {
+    User,
+    Nice,
+    System,
+    Irq,
+    Softirq,
+    Iowait,
+    Steal,
+}
+
+impl CpuTime {
+    pub const COUNT: usize = 7;
+    pub const ALL: [Self; Self::COUNT] = [
+        Self::User,
+        Self::Nice,
+        Self::System,
+        Self::Irq,
+        Self::Softirq,
+        Self::Iowait,
+        Self::Steal,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::User => "user",
+            Self::Nice => "nice",
+            Self::System => "system",
+            Self::Irq => "irq",
+            Self::Softirq => "softirq",
+            Self::Iowait => "iowait",
+            Self::Steal => "steal",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|t| t.name() == name)
+    }
+}
+
+/// The fraction of time a CPU spent in each [`CpuTime`] category
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct CpuTimes(pub [f64; CpuTime::COUNT]);
+
+impl std::ops::Index<CpuTime> for CpuTimes {
+    type Output = f64;
+
+    #[inline]
+    fn index(&self, index: CpuTime) -> &Self::Output {
+        &self.0[index as usize]
+    }
+}
+
+impl CpuTimes {
+    /// Get the total fraction of time spent in the categories included in
+    /// `set`.
+    pub fn sum(&self, set: CpuTimeSet) -> f64 {
+        CpuTime::ALL
+            .iter()
+            .filter(|&&t| set.contains(t))
+            .map(|&t| self[t])
+            .sum()
+    }
+}
+
+/// A set of [`CpuTime`]s
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CpuTimeSet(u8);
+
+impl CpuTimeSet {
+    pub const EMPTY: Self = Self(0);
+
+    #[inline]
+    pub fn contains(self, t: CpuTime) -> bool {
+        self.0 & (1 << t as u8) != 0
+    }
+
+    #[inline]
+    pub fn insert(&mut self, t: CpuTime) {
+        self.0 |= 1 << t as u8;
+    }
+
+    #[inline]
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl FromIterator<CpuTime> for CpuTimeSet {
+    fn from_iter<I: IntoIterator<Item = CpuTime>>(iter: I) -> Self {
+        let mut set = Self::EMPTY;
+        for t in iter {
+            set.insert(t);
+        }
+        set
+    }
+}
+
+/// The source of CPU usage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// The whole system
+    Host,
+    /// The cgroup containing Zellij, with virtual CPUs representing its CPU
+    /// quota
+    Cgroup,
 }
 
 #[inline]
-pub fn current_system() -> Option<Box<dyn System>> {
+pub fn current_system(backend: Backend) -> Option<Box<dyn System>> {
     // TODO: support other systems
-    Some(Box::new(linux::System::default()))
+    Some(match backend {
+        Backend::Host => Box::new(linux::System::default()),
+        Backend::Cgroup => Box::new(linux::CgroupSystem::default()),
+    })
 }